@@ -10,3 +10,6 @@
 #include "bash/input.h"
 #include "bash/dispose_cmd.h"
 #include "bash/builtins.h"
+
+// parser token values used for command connectors
+#include "bash/y.tab.h"
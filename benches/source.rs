@@ -0,0 +1,73 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use scallop::source::cache::{self, Cache};
+use scallop::{source, Shell};
+
+/// Generate an eclass-like file with many functions and top-level assignments.
+fn generate(funcs: usize) -> String {
+    let mut data = String::new();
+    for i in 0..funcs {
+        writeln!(
+            data,
+            r#"
+VAR_{i}="value ${{VAR_{i}:-default}}"
+func_{i}() {{
+    local x=$1 y=${{2:-}}
+    if [[ -n ${{x}} && ${{x}} == @(a|b)* ]]; then
+        case ${{y}} in
+            foo|bar) echo "${{x}}-${{y}}" ;;
+            *) for z in a b c; do x+=${{z}}; done ;;
+        esac
+    fi
+    [[ ${{x}} =~ ^[0-9]+$ ]] && return $(( x % 2 ))
+}}"#
+        )
+        .unwrap();
+    }
+    data
+}
+
+fn time<F: FnMut()>(iterations: u32, mut func: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        func();
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    Shell::init().unwrap();
+    source::string("shopt -s extglob").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bench.eclass");
+    fs::write(&path, generate(200)).unwrap();
+    let iterations = 200;
+
+    let uncached = time(iterations, || {
+        source::file(&path).unwrap();
+    });
+
+    let mut cache = Cache::new();
+    cache.source(&path).unwrap();
+    let cached = time(iterations, || {
+        cache.source(&path).unwrap();
+    });
+
+    // installed caches are also used for files sourced via the builtins
+    let outer = dir.path().join("outer.eclass");
+    fs::write(&outer, format!("source {}\n", path.display())).unwrap();
+    cache::set(Cache::new());
+    source::file(&outer).unwrap();
+    let nested = time(iterations, || {
+        source::file(&outer).unwrap();
+    });
+    cache::take();
+
+    println!("source::file: {uncached:?}/iter");
+    println!("Cache::source: {cached:?}/iter");
+    println!("speedup: {:.2}x", uncached.as_secs_f64() / cached.as_secs_f64());
+    println!("nested via installed cache: {nested:?}/iter");
+}
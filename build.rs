@@ -20,9 +20,44 @@ impl ParseCallbacks for BashCallback {
             "SHELL_VAR" => Some("ShellVar".into()),
             "ARRAY" => Some("Array".into()),
             "command" => Some("Command".into()),
+            "COMMAND" => Some("Command".into()),
+            "redirect" => Some("Redirect".into()),
+            "REDIRECT" => Some("Redirect".into()),
+            "simple_com" => Some("SimpleCom".into()),
+            "SIMPLE_COM" => Some("SimpleCom".into()),
+            "connection" => Some("Connection".into()),
+            "CONNECTION" => Some("Connection".into()),
+            "for_com" => Some("ForCom".into()),
+            "FOR_COM" => Some("ForCom".into()),
+            "arith_for_com" => Some("ArithForCom".into()),
+            "ARITH_FOR_COM" => Some("ArithForCom".into()),
+            "select_com" => Some("SelectCom".into()),
+            "SELECT_COM" => Some("SelectCom".into()),
+            "case_com" => Some("CaseCom".into()),
+            "CASE_COM" => Some("CaseCom".into()),
+            "pattern_list" => Some("PatternList".into()),
+            "PATTERN_LIST" => Some("PatternList".into()),
+            "if_com" => Some("IfCom".into()),
+            "IF_COM" => Some("IfCom".into()),
+            "while_com" => Some("WhileCom".into()),
+            "WHILE_COM" => Some("WhileCom".into()),
+            "function_def" => Some("FunctionDef".into()),
+            "FUNCTION_DEF" => Some("FunctionDef".into()),
+            "group_com" => Some("GroupCom".into()),
+            "GROUP_COM" => Some("GroupCom".into()),
+            "subshell_com" => Some("SubshellCom".into()),
+            "SUBSHELL_COM" => Some("SubshellCom".into()),
+            "coproc_com" => Some("CoprocCom".into()),
+            "COPROC_COM" => Some("CoprocCom".into()),
+            "arith_com" => Some("ArithCom".into()),
+            "ARITH_COM" => Some("ArithCom".into()),
+            "cond_com" => Some("CondCom".into()),
+            "COND_COM" => Some("CondCom".into()),
             "builtin" => Some("Builtin".into()),
             // global mutables
             "global_command" => Some("GLOBAL_COMMAND".into()),
+            "line_number" => Some("LINE_NUMBER".into()),
+            "EOF_Reached" => Some("EOF_REACHED".into()),
             "this_command_name" => Some("CURRENT_COMMAND".into()),
             "temporary_env" => Some("TEMPORARY_ENV".into()),
             "ifs_value" => Some("IFS".into()),
@@ -111,10 +146,21 @@ fn main() {
         .allowlist_var("global_command")
         .allowlist_function("copy_command")
         .allowlist_var("CMD_.*")
+        .allowlist_var("W_.*")
+        .allowlist_var("REDIR_.*")
+        .allowlist_var("CASEPAT_.*")
+        .allowlist_var("COND_.*")
+        .allowlist_type("command_type")
+        .allowlist_type("r_instruction")
+        // y.tab.h
+        .allowlist_var("AND_AND")
+        .allowlist_var("OR_OR")
         // execute_cmd.h
         .allowlist_var("this_command_name")
         .allowlist_var("subshell_level")
         .allowlist_var("executing_builtin")
+        .allowlist_var("line_number")
+        .allowlist_function("executing_line_number")
         .allowlist_function("execute_command")
         .allowlist_function("execute_shell_function")
         // shell.h
@@ -124,6 +170,7 @@ fn main() {
         .allowlist_function("lib_reset")
         .allowlist_function("set_shell_name")
         .allowlist_var("shell_name")
+        .allowlist_var("EOF_Reached")
         .allowlist_var("dist_version")
         .allowlist_var("patch_level")
         .allowlist_var("EXECUTION_FAILURE")
@@ -141,16 +188,26 @@ fn main() {
         .allowlist_function("find_variable")
         .allowlist_function("push_context")
         .allowlist_function("pop_context")
+        .allowlist_function("push_dollar_vars")
+        .allowlist_function("pop_dollar_vars")
         .allowlist_var("temporary_env")
         .allowlist_var("att_.*") // variable attributes
         // externs.h
         .allowlist_function("parse_command")
         .allowlist_function("strvec_dispose")
         .allowlist_function("strvec_to_word_list")
+        .allowlist_function("make_command_string")
+        .allowlist_function("parse_string_to_word_list")
+        .allowlist_function("sh_single_quote")
+        .allowlist_function("sh_double_quote")
+        .allowlist_function("sh_backslash_quote")
+        .allowlist_function("ansic_quote")
+        .allowlist_function("ansic_shouldquote")
         // input.h
         .allowlist_function("with_input_from_string")
         .allowlist_function("push_stream")
         .allowlist_function("pop_stream")
+        .allowlist_function("yy_input_name")
         // dispose_cmd.h
         .allowlist_function("dispose_command")
         .allowlist_function("dispose_words")
@@ -161,14 +218,22 @@ fn main() {
         .allowlist_function("builtin_address_internal")
         .allowlist_function("get_minus_o_opts")
         .allowlist_function("get_shopt_options")
+        .allowlist_function("remember_args")
+        .allowlist_function("shift_args")
+        .allowlist_function("number_of_args")
         .allowlist_var("SEVAL_.*")
         // subst.h
         .allowlist_function("expand_string_to_string")
         .allowlist_function("list_string")
+        .allowlist_function("list_rest_of_args")
+        .allowlist_function("string_quote_removal")
         .allowlist_var("ifs_value")
         .allowlist_var("ASS_.*")
         // array.h
         .allowlist_function("array_to_argv")
+        .allowlist_function("array_shift")
+        .allowlist_function("array_shift_element")
+        .allowlist_function("array_dispose_element")
         // builtins.h
         .allowlist_var("BUILTIN_ENABLED")
         .allowlist_var("STATIC_BUILTIN")
@@ -2,7 +2,7 @@ use scallop::{builtins, Shell};
 
 fn main() {
     // initialize shell
-    Shell::init();
+    Shell::init().expect("failed initializing shell");
 
     // load and enable builtins
     let builtins = &[builtins::profile::BUILTIN];
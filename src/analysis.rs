@@ -0,0 +1,489 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::builtins::shell_builtins;
+use crate::command::ast::{Node, NodeKind, Simple, Word};
+use crate::command::visit::{visit_script, walk_compound, walk_simple, Visitor};
+use crate::command::{parse_script, ParsedCommand};
+use crate::Error;
+
+/// Dependencies of bash code determined without executing it.
+///
+/// Only literal names are tracked, e.g. `$cmd args` doesn't register a called command and
+/// `source "${dir}"/file` doesn't register a sourced file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Variables expanded via parameter expansion or referenced in arithmetic contexts.
+    pub variables_read: BTreeSet<String>,
+    /// Variables set via assignments, loop variables, arithmetic, or builtins.
+    pub variables_assigned: BTreeSet<String>,
+    pub functions_defined: BTreeSet<String>,
+    /// Names of all simple commands run, including builtins and functions.
+    pub commands_called: BTreeSet<String>,
+    /// Called commands that aren't shell builtins or functions defined by the code itself.
+    pub external_commands: BTreeSet<String>,
+    /// Literal arguments to the `source` and `.` builtins.
+    pub sourced: BTreeSet<String>,
+    /// Literal arguments to `inherit`.
+    pub inherited: BTreeSet<String>,
+}
+
+/// Analyze a string of bash code.
+pub fn analyze<S: AsRef<str>>(s: S) -> crate::Result<Analysis> {
+    let cmds = parse_script(s)?;
+    Ok(analyze_commands(&cmds))
+}
+
+/// Analyze a file of bash code.
+pub fn analyze_file<P: AsRef<Path>>(path: P) -> crate::Result<Analysis> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path)
+        .map_err(|e| Error::IO(format!("failed reading file: {path:?}: {e}"), e))?;
+    analyze(data)
+}
+
+/// Analyze previously parsed commands.
+pub fn analyze_commands(cmds: &[ParsedCommand]) -> Analysis {
+    let mut analyzer = Analyzer::default();
+    visit_script(&mut analyzer, cmds);
+
+    let mut analysis = analyzer.0;
+    let (enabled, disabled) = shell_builtins();
+    analysis.external_commands = analysis
+        .commands_called
+        .iter()
+        .filter(|s| !enabled.contains(*s) && !disabled.contains(*s))
+        .filter(|s| !analysis.functions_defined.contains(*s))
+        .cloned()
+        .collect();
+    analysis
+}
+
+#[derive(Default)]
+struct Analyzer(Analysis);
+
+impl Analyzer {
+    /// Register the variable name for a given assignment word, e.g. `VAR=1` or `arr[0]+=a`.
+    fn assign(&mut self, s: &str) {
+        let end = s.find(['=', '[', '+']).unwrap_or(s.len());
+        let name = &s[..end];
+        if is_identifier(name) {
+            self.0.variables_assigned.insert(name.to_string());
+        }
+    }
+
+    /// Register variables assigned by builtins via their arguments.
+    fn builtin_assignments(&mut self, name: &str, args: &[Word]) {
+        let mut args = args.iter().map(|w| w.as_str());
+        match name {
+            "declare" | "typeset" | "local" | "export" | "readonly" => {
+                for arg in args.filter(|s| !s.starts_with(['-', '+'])) {
+                    self.assign(arg);
+                }
+            }
+            "read" => {
+                let mut args = args.peekable();
+                while let Some(arg) = args.next_if(|s| s.starts_with('-')) {
+                    match arg {
+                        "-a" => {
+                            if let Some(s) = args.next() {
+                                self.assign(s);
+                            }
+                        }
+                        "-d" | "-i" | "-n" | "-N" | "-p" | "-t" | "-u" => {
+                            args.next();
+                        }
+                        _ => (),
+                    }
+                }
+                for arg in args {
+                    self.assign(arg);
+                }
+            }
+            "let" => {
+                for arg in args {
+                    self.arith(arg);
+                }
+            }
+            "printf" => {
+                let mut args = args.peekable();
+                while let Some(arg) = args.next_if(|s| s.starts_with('-') && *s != "--") {
+                    match arg.strip_prefix("-v") {
+                        Some("") => {
+                            if let Some(s) = args.next() {
+                                self.assign(s);
+                            }
+                        }
+                        Some(s) => self.assign(s),
+                        None => (),
+                    }
+                }
+            }
+            "mapfile" | "readarray" => match args.next_back() {
+                Some(s) if !s.starts_with('-') => self.assign(s),
+                _ => self.assign("MAPFILE"),
+            },
+            _ => (),
+        }
+    }
+
+    /// Analyze the code contained in a command substitution.
+    fn nested(&mut self, s: &str) {
+        // unparseable substitutions are ignored since bash would fail on them at runtime
+        if let Ok(cmds) = parse_script(s) {
+            for cmd in cmds {
+                self.visit_node(&cmd.command.ast());
+            }
+        }
+    }
+
+    /// Register the variables expanded in a word.
+    fn scan(&mut self, s: &str) {
+        let bytes = s.as_bytes();
+        let (mut single, mut double) = (false, false);
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' if !single => i += 1,
+                b'\'' if !double => single = !single,
+                b'"' if !single => double = !double,
+                b'`' if !single => {
+                    if let Some(end) = s[i + 1..].find('`') {
+                        self.nested(&s[i + 1..i + 1 + end]);
+                        i += end + 1;
+                    }
+                }
+                b'$' if !single => i += self.expansion(&s[i + 1..]),
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    /// Register variables for an expansion following a `$`, returning the number of bytes
+    /// consumed.
+    fn expansion(&mut self, s: &str) -> usize {
+        let bytes = s.as_bytes();
+        match bytes.first() {
+            Some(b'{') => {
+                let end = closing(bytes, 0, b'{', b'}').unwrap_or(s.len());
+                let inner = &s[1..end];
+                let inner = inner.strip_prefix(['#', '!']).unwrap_or(inner);
+                let len = identifier_len(inner.as_bytes());
+                if len > 0 {
+                    self.0.variables_read.insert(inner[..len].to_string());
+                }
+                self.scan(&inner[len..]);
+                end + 1
+            }
+            Some(b'(') if bytes.get(1) == Some(&b'(') => {
+                let end = closing(bytes, 0, b'(', b')').unwrap_or(s.len());
+                self.arith(s[2..end].trim_end_matches(')'));
+                end + 1
+            }
+            Some(b'(') => {
+                let end = closing(bytes, 0, b'(', b')').unwrap_or(s.len());
+                self.nested(&s[1..end]);
+                end + 1
+            }
+            _ => {
+                let len = identifier_len(s.as_bytes());
+                if len > 0 {
+                    self.0.variables_read.insert(s[..len].to_string());
+                }
+                len
+            }
+        }
+    }
+
+    /// Register the variables referenced or assigned in an arithmetic expression.
+    fn arith(&mut self, s: &str) {
+        self.scan(s);
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                // skip expansions that were handled by the scan
+                b'$' => {
+                    i += match bytes.get(i + 1) {
+                        Some(b'{') => closing(bytes, i + 1, b'{', b'}').map(|n| n - i),
+                        Some(b'(') => closing(bytes, i + 1, b'(', b')').map(|n| n - i),
+                        _ => None,
+                    }
+                    .unwrap_or_else(|| identifier_len(&bytes[i + 1..]))
+                }
+                // skip numbers including hex and base#value formats
+                c if c.is_ascii_digit() => {
+                    while i + 1 < bytes.len()
+                        && (bytes[i + 1].is_ascii_alphanumeric() || bytes[i + 1] == b'#')
+                    {
+                        i += 1;
+                    }
+                }
+                _ => {
+                    let len = identifier_len(&bytes[i..]);
+                    if len > 0 {
+                        let name = s[i..i + len].to_string();
+                        match arith_op(bytes, i, len) {
+                            ArithOp::Read => {
+                                self.0.variables_read.insert(name);
+                            }
+                            ArithOp::Assign => {
+                                self.0.variables_assigned.insert(name);
+                            }
+                            ArithOp::Update => {
+                                self.0.variables_read.insert(name.clone());
+                                self.0.variables_assigned.insert(name);
+                            }
+                        }
+                        i += len - 1;
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+impl Visitor for Analyzer {
+    fn visit_simple(&mut self, cmd: &Simple, line: i32) {
+        if let Some(name) = cmd.name().and_then(literal) {
+            let args = cmd.args();
+            match name.as_str() {
+                "source" | "." => {
+                    if let Some(path) = args.first().and_then(literal) {
+                        self.0.sourced.insert(path);
+                    }
+                }
+                "inherit" => self.0.inherited.extend(args.iter().filter_map(literal)),
+                s => self.builtin_assignments(s, args),
+            }
+            self.0.commands_called.insert(name);
+        }
+        walk_simple(self, cmd, line);
+    }
+
+    fn visit_word(&mut self, word: &Word, _line: i32) {
+        self.scan(word.as_str());
+    }
+
+    fn visit_assignment(&mut self, word: &Word, _line: i32) {
+        let s = word.as_str();
+        self.assign(s);
+        if let Some((_, value)) = s.split_once('=') {
+            self.scan(value);
+        }
+    }
+
+    fn visit_function(&mut self, name: &Word, body: &Node, _line: i32) {
+        self.0.functions_defined.insert(name.to_string());
+        self.visit_node(body);
+    }
+
+    fn visit_compound(&mut self, node: &Node) {
+        match &node.kind {
+            NodeKind::For { var, .. } | NodeKind::Select { var, .. } => {
+                self.assign(var.as_str());
+            }
+            NodeKind::Arith(exprs) => {
+                for w in exprs {
+                    self.arith(w.as_str());
+                }
+                return;
+            }
+            NodeKind::ArithFor {
+                init,
+                test,
+                step,
+                body,
+            } => {
+                for w in init.iter().chain(test).chain(step) {
+                    self.arith(w.as_str());
+                }
+                self.visit_node(body);
+                return;
+            }
+            _ => (),
+        }
+        walk_compound(self, node);
+    }
+}
+
+/// Variable access performed by an arithmetic expression.
+enum ArithOp {
+    Read,
+    Assign,
+    Update,
+}
+
+/// Determine how an arithmetic expression accesses the variable at the given position.
+fn arith_op(bytes: &[u8], start: usize, len: usize) -> ArithOp {
+    let before = &bytes[..start];
+    let before = match before.iter().rposition(|c| !c.is_ascii_whitespace()) {
+        Some(n) => &before[..=n],
+        None => before,
+    };
+    if before.ends_with(b"++") || before.ends_with(b"--") {
+        return ArithOp::Update;
+    }
+
+    // skip array subscripts
+    let mut end = start + len;
+    if bytes.get(end) == Some(&b'[') {
+        end = closing(bytes, end, b'[', b']').map_or(bytes.len(), |n| n + 1);
+    }
+
+    let after = &bytes[end..];
+    let after = match after.iter().position(|c| !c.is_ascii_whitespace()) {
+        Some(n) => &after[n..],
+        None => after,
+    };
+    let updates: [&[u8]; 12] =
+        [b"<<=", b">>=", b"+=", b"-=", b"*=", b"/=", b"%=", b"&=", b"^=", b"|=", b"++", b"--"];
+    if updates.iter().any(|op| after.starts_with(op)) {
+        ArithOp::Update
+    } else if after.starts_with(b"=") && !after.starts_with(b"==") {
+        ArithOp::Assign
+    } else {
+        ArithOp::Read
+    }
+}
+
+/// Return the length of the valid variable identifier at the start of a string.
+fn identifier_len(s: &[u8]) -> usize {
+    match s.first() {
+        Some(c) if c.is_ascii_alphabetic() || *c == b'_' => s
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+            .count(),
+        _ => 0,
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && identifier_len(s.as_bytes()) == s.len()
+}
+
+/// Return the index of the delimiter closing the one at the given position.
+fn closing(bytes: &[u8], start: usize, open: u8, close: u8) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in bytes.iter().enumerate().skip(start) {
+        match *c {
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Return the unquoted value of a word if it doesn't require expansion.
+fn literal(word: &Word) -> Option<String> {
+    let s = word.as_str();
+    if s.contains(['$', '`', '*', '?', '[']) {
+        return None;
+    }
+
+    let mut value = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => (),
+            '\\' => value.extend(chars.next()),
+            c => value.push(c),
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn set(values: &[&str]) -> BTreeSet<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_analyze() {
+        let script = [
+            "inherit foo 'bar'",
+            "source \"${FILESDIR}\"/x.sh",
+            ". ./lib.sh",
+            "VAR=1 arr[1]+=a",
+            "local L=2 -r M",
+            "read -p prompt -a ARR R1 R2",
+            "func() { echo \"$VAR ${OTHER:-$DEF} ${#LEN}\" | sed -e 's/$X//'; helper; }",
+            "for i in $(seq $N) `echo $BT`; do (( total += i * 0x1F )); done",
+            "x=$(( COUNT + ${OFFSET} ))",
+        ]
+        .join("\n");
+        let a = analyze(script).unwrap();
+
+        assert_eq!(a.inherited, set(&["foo", "bar"]));
+        assert_eq!(a.sourced, set(&["./lib.sh"]));
+        assert_eq!(a.functions_defined, set(&["func"]));
+        assert_eq!(
+            a.variables_assigned,
+            set(&["VAR", "arr", "L", "M", "ARR", "R1", "R2", "i", "total", "x"])
+        );
+        assert_eq!(
+            a.variables_read,
+            set(&[
+                "FILESDIR", "VAR", "OTHER", "DEF", "LEN", "N", "BT", "total", "i", "COUNT",
+                "OFFSET"
+            ])
+        );
+        assert_eq!(
+            a.commands_called,
+            set(&["inherit", "source", ".", "local", "read", "echo", "sed", "helper", "seq"])
+        );
+
+        // builtins and locally defined functions aren't external
+        for s in ["sed", "helper", "seq"] {
+            assert!(a.external_commands.contains(s));
+        }
+        for s in ["source", ".", "local", "read", "echo", "func"] {
+            assert!(!a.external_commands.contains(s));
+        }
+
+        // arithmetic assignments
+        let script = [
+            "(( j++, --k, arr[n] = 3, y = z == 1 ))",
+            "let 'a += 1' b=c",
+            "printf -v out %s x; printf -vfmt %s y; printf -- -v",
+        ]
+        .join("\n");
+        let a = analyze(script).unwrap();
+        assert_eq!(a.variables_assigned, set(&["j", "k", "arr", "y", "a", "b", "out", "fmt"]));
+        assert_eq!(a.variables_read, set(&["j", "k", "n", "z", "a", "c"]));
+
+        // non-ASCII arithmetic expressions
+        let a = analyze("(( é + x ))").unwrap();
+        assert_eq!(a.variables_read, set(&["x"]));
+    }
+
+    #[test]
+    fn test_analyze_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "foo() {{ bar; }}\nfoo").unwrap();
+        let a = analyze_file(file.path()).unwrap();
+        assert_eq!(a.functions_defined, set(&["foo"]));
+        assert_eq!(a.commands_called, set(&["foo", "bar"]));
+        assert_eq!(a.external_commands, set(&["bar"]));
+
+        assert!(analyze_file("/nonexistent/path").is_err());
+        assert!(analyze("a )").is_err());
+    }
+}
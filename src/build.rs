@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use crate::{shell, syntax, Error, Shell};
+
+/// Check bash files for syntax errors from a build script.
+///
+/// Paths are relative to the crate root and registered so the build script reruns when they
+/// change. Syntax errors are emitted as cargo warnings and returned as an error, failing the
+/// build.
+///
+/// ```ignore
+/// // build.rs
+/// fn main() {
+///     scallop::build::check(["bash/helpers.sh"]).unwrap();
+/// }
+///
+/// // src/lib.rs
+/// const HELPERS: &str = scallop::include_bash!("bash/helpers.sh");
+/// ```
+pub fn check<I, P>(paths: I) -> crate::Result<()>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    if !shell::initialized() {
+        Shell::init()?;
+    }
+
+    let mut errors = vec![];
+    for path in paths {
+        let path = path.as_ref();
+        println!("cargo:rerun-if-changed={}", path.display());
+        for diagnostic in syntax::check_file(path)? {
+            println!("cargo:warning={diagnostic}");
+            errors.push(diagnostic.to_string());
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Base(format!("invalid bash:\n{}", errors.join("\n"))))
+    }
+}
+
+/// Embed a bash file relative to the crate root, checked at build time via [`check`].
+#[macro_export]
+macro_rules! include_bash {
+    ($path:literal) => {
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $path))
+    };
+}
+pub use include_bash;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        assert!(check(["scripts/build-libbash"]).is_ok());
+        assert!(check(["/nonexistent/path"]).is_err());
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "a\nb)").unwrap();
+        let err = check([file.path()]).unwrap_err().to_string();
+        assert!(err.contains(&format!("{}: line 2: ", file.path().display())), "{err}");
+    }
+
+    #[test]
+    fn test_include_bash() {
+        let code = include_bash!("scripts/build-libbash");
+        assert!(code.starts_with("#!/bin/bash"));
+    }
+}
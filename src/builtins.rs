@@ -3,12 +3,13 @@ use std::ffi::{CStr, CString};
 use std::hash::{Hash, Hasher};
 use std::os::raw::{c_char, c_int};
 use std::process::ExitStatus;
-use std::{fmt, mem, process, ptr};
+use std::{fmt, io, mem, process, ptr};
 
 use bitflags::bitflags;
 use nix::sys::signal;
 
-use crate::shell::{in_subshell, kill, Shell};
+use crate::error::{channel, location, BashError, ErrorKind};
+use crate::shell::{in_subshell, kill};
 use crate::{bash, Error};
 
 mod _bash;
@@ -404,10 +405,7 @@ impl From<ExecStatus> for i32 {
 
 impl From<Error> for ExecStatus {
     fn from(e: Error) -> ExecStatus {
-        match e {
-            Error::Status(s, _) => s,
-            _ => ExecStatus::Failure(1),
-        }
+        e.status().unwrap_or(ExecStatus::Failure(1))
     }
 }
 
@@ -455,7 +453,26 @@ pub fn handle_error(cmd: &str, err: Error) -> ExecStatus {
     match cfg!(feature = "plugin") {
         true => eprintln!("{msg}"),
         false => {
-            Shell::set_shm_error(&msg);
+            let errno = match &err {
+                Error::IO(_, e) => e.raw_os_error(),
+                _ => None,
+            };
+            let e = match errno {
+                // system errors keep their errno value
+                Some(n) => Error::IO(msg, io::Error::from_raw_os_error(n)),
+                None => {
+                    let (file, line) = location(ErrorKind::Builtin);
+                    Error::Bash(BashError {
+                        file,
+                        line,
+                        kind: err.kind(),
+                        command: Some(cmd.to_string()),
+                        status: Some(err.status().unwrap_or(ExecStatus::Failure(1))),
+                        message: msg,
+                    })
+                }
+            };
+            channel::send(&e);
             if let Error::Bail(_) = err {
                 // TODO: send SIGTERM to background jobs (use jobs builtin)
                 if in_subshell() {
@@ -483,6 +500,7 @@ macro_rules! make_builtin {
             let words = list.into_words(false);
             let args: Vec<_> = words.into_iter().collect();
 
+            let _errors = $crate::error::ScopedErrors::new();
             let ret = match $func(&args) {
                 Ok(ret) => ret,
                 Err(e) => $crate::builtins::handle_error($name, e),
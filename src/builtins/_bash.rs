@@ -1,11 +1,12 @@
 use crate::bash;
 use crate::builtins::ExecStatus;
 use crate::command::cmd_scope;
-use crate::error::ok_or_error;
+use crate::error::{ok_or_error, ScopedErrors};
 use crate::traits::*;
 
 /// Run the `declare` builtin with the given arguments.
 pub fn declare(args: &[&str]) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
     let args = Words::from_iter(args.iter().copied());
     cmd_scope("declare", || unsafe {
         bash::declare_builtin((&args).into());
@@ -16,6 +17,7 @@ pub fn declare(args: &[&str]) -> crate::Result<ExecStatus> {
 
 /// Run the `local` builtin with the given arguments.
 pub fn local(args: &[&str]) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
     let args = Words::from_iter(args.iter().copied());
     cmd_scope("local", || unsafe {
         bash::local_builtin((&args).into());
@@ -26,6 +28,7 @@ pub fn local(args: &[&str]) -> crate::Result<ExecStatus> {
 
 /// Run the `set` builtin with the given arguments.
 pub fn set(args: &[&str]) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
     let args = Words::from_iter(args.iter().copied());
     cmd_scope("set", || unsafe {
         bash::set_builtin((&args).into());
@@ -36,6 +39,7 @@ pub fn set(args: &[&str]) -> crate::Result<ExecStatus> {
 
 /// Run the `shopt` builtin with the given arguments.
 pub fn shopt(args: &[&str]) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
     let args = Words::from_iter(args.iter().copied());
     cmd_scope("shopt", || unsafe {
         bash::shopt_builtin((&args).into());
@@ -54,10 +58,11 @@ mod tests {
     #[test]
     fn test_local() {
         bind("VAR", "outer", None, None).unwrap();
-        bash_func("func_name", || {
+        bash_func("func_name", &[], || {
             local(&["VAR=inner"]).unwrap();
             assert_eq!(string_value("VAR").unwrap(), "inner");
-        });
+        })
+        .unwrap();
         assert_eq!(string_value("VAR").unwrap(), "outer");
     }
 }
@@ -19,11 +19,12 @@ pub(crate) fn run(args: &[&str]) -> crate::Result<ExecStatus> {
 
     let mut loops = 0;
     let mut elapsed = Duration::new(0, 0);
-    let orig_cmd = args.join(" ");
+    let orig_cmd = Command::new(args.join(" "), None)?;
     eprintln!("profiling: {orig_cmd}");
 
-    // force success so the shell doesn't exit prematurely while profiling
-    let cmd_str = format!("{orig_cmd} || :");
+    // Force success so the shell doesn't exit prematurely while profiling, the group is
+    // terminated on a separate line since rendered here-documents end with their delimiter.
+    let cmd_str = format!("{{ {orig_cmd}\n}} || :");
     let cmd = Command::new(cmd_str, None)?;
     let start = Instant::now();
     while elapsed.as_secs() < 3 {
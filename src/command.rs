@@ -1,13 +1,20 @@
-use std::ffi::{CStr, CString};
-use std::ptr;
+use std::borrow::Cow;
+use std::ffi::{CStr, CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::{fmt, fs, ptr};
 
 use bitflags::bitflags;
 use once_cell::sync::Lazy;
 
 use crate::bash;
 use crate::builtins::ExecStatus;
-use crate::Error;
+use crate::error::{last_error, ErrorKind, ScopedErrorKind, ScopedErrors};
+use crate::{quote, Error};
+
+pub mod ast;
+pub mod visit;
 
 bitflags! {
     /// Flag values used with commands.
@@ -23,6 +30,9 @@ bitflags! {
     }
 }
 
+// indentation width used by bash's command printer
+const INDENT: usize = 4;
+
 #[derive(Debug)]
 pub struct Command {
     ptr: *mut bash::Command,
@@ -37,10 +47,188 @@ impl Command {
         Ok(cmd)
     }
 
+    /// Return the typed syntax tree for the parsed command.
+    pub fn ast(&self) -> ast::Node {
+        unsafe { ast::Node::from_raw(&*self.ptr) }
+    }
+
+    /// Render the command as normalized bash source using the given indentation width.
+    ///
+    /// Lines inside multi-line strings, command substitutions, and here-document bodies are
+    /// left as is.
+    pub fn to_string_indented(&self, width: usize) -> String {
+        let mut scanner = Scanner::default();
+        let mut s = String::new();
+        for line in self.to_string().split_inclusive('\n') {
+            if scanner.at_top_level() {
+                let trimmed = line.trim_start_matches(' ');
+                let n = line.len() - trimmed.len();
+                s.push_str(&" ".repeat(n / INDENT * width + n % INDENT));
+                s.push_str(trimmed);
+            } else {
+                s.push_str(line);
+            }
+            scanner.scan(line);
+        }
+        s
+    }
+
     pub fn execute(&self) -> crate::Result<ExecStatus> {
         match unsafe { bash::execute_command(self.ptr) } {
             0 => Ok(ExecStatus::Success),
-            n => Err(Error::Status(ExecStatus::Failure(n), "command failed".into())),
+            n => Err(Error::Status(ExecStatus::Failure(n), format!("command failed: {self}"))),
+        }
+    }
+}
+
+/// Quoting context tracked while scanning rendered commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Single,
+    Double,
+    AnsiC,
+    Backtick,
+    /// Command substitution or arithmetic with its open parenthesis count.
+    Parens(usize),
+}
+
+/// Line-based scanner tracking whether rendered command lines start at the top level.
+#[derive(Debug, Default)]
+struct Scanner {
+    stack: Vec<Context>,
+    /// Delimiters of here-documents started on the current line.
+    pending: Vec<(String, bool)>,
+    /// Delimiters of here-documents whose bodies are being read.
+    bodies: Vec<(String, bool)>,
+}
+
+impl Scanner {
+    fn at_top_level(&self) -> bool {
+        self.stack.is_empty() && self.bodies.is_empty()
+    }
+
+    fn scan(&mut self, line: &str) {
+        if let Some((eof, strip)) = self.bodies.first() {
+            let mut s = line.trim_end_matches('\n');
+            if *strip {
+                s = s.trim_start_matches('\t');
+            }
+            if s == eof {
+                self.bodies.remove(0);
+            }
+            return;
+        }
+
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let next = bytes.get(i + 1).copied();
+            match (self.stack.last().copied(), bytes[i]) {
+                (Some(Context::Single), b'\'') => {
+                    self.stack.pop();
+                }
+                (Some(Context::Single), _) => (),
+                (Some(Context::AnsiC | Context::Backtick | Context::Double), b'\\') => i += 1,
+                (Some(Context::AnsiC), b'\'') | (Some(Context::Double), b'"') => {
+                    self.stack.pop();
+                }
+                (Some(Context::Backtick), b'`') => {
+                    self.stack.pop();
+                }
+                (Some(Context::AnsiC | Context::Backtick), _) => (),
+                (Some(Context::Double), b'`') => self.stack.push(Context::Backtick),
+                (Some(Context::Double), b'$') if next == Some(b'(') => {
+                    self.stack.push(Context::Parens(1));
+                    i += 1;
+                }
+                (Some(Context::Double), _) => (),
+                (_, b'\\') => i += 1,
+                (_, b'\'') => self.stack.push(Context::Single),
+                (_, b'"') => self.stack.push(Context::Double),
+                (_, b'`') => self.stack.push(Context::Backtick),
+                (_, b'$') if next == Some(b'\'') => {
+                    self.stack.push(Context::AnsiC);
+                    i += 1;
+                }
+                (_, b'$') if next == Some(b'(') => {
+                    self.stack.push(Context::Parens(1));
+                    i += 1;
+                }
+                (Some(Context::Parens(n)), b'(') => {
+                    self.stack.pop();
+                    self.stack.push(Context::Parens(n + 1));
+                }
+                (Some(Context::Parens(n)), b')') => {
+                    self.stack.pop();
+                    if n > 1 {
+                        self.stack.push(Context::Parens(n - 1));
+                    }
+                }
+                // arithmetic commands can contain shift operators
+                (None, b'(') if next == Some(b'(') => {
+                    self.stack.push(Context::Parens(2));
+                    i += 1;
+                }
+                (None, b'<') if next == Some(b'<') && bytes.get(i + 2) != Some(&b'<') => {
+                    i += self.here_doc(&line[i + 2..]) + 1;
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+
+        if self.stack.is_empty() {
+            self.bodies.append(&mut self.pending);
+        }
+    }
+
+    /// Register the here-document delimiter following a `<<` operator, returning the number
+    /// of bytes consumed.
+    fn here_doc(&mut self, s: &str) -> usize {
+        let strip = s.starts_with('-');
+        let start = usize::from(strip);
+        let word = s[start..].trim_start_matches([' ', '\t']);
+        let start = s.len() - word.len();
+
+        let (mut single, mut double, mut escaped) = (false, false, false);
+        let mut eof = String::new();
+        let mut len = word.len();
+        for (i, c) in word.char_indices() {
+            match c {
+                _ if escaped => {
+                    eof.push(c);
+                    escaped = false;
+                }
+                '\\' if !single => escaped = true,
+                '\'' if !double => single = !single,
+                '"' if !single => double = !double,
+                c if !single && !double && (c.is_whitespace() || ";|&<>()".contains(c)) => {
+                    len = i;
+                    break;
+                }
+                c => eof.push(c),
+            }
+        }
+
+        self.pending.push((eof, strip));
+        start + len
+    }
+}
+
+/// Render the command as normalized bash source using bash's command printer.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // bash returns a pointer to its internal print buffer which must not be freed
+        let s = unsafe { CStr::from_ptr(bash::make_command_string(self.ptr)) };
+        write!(f, "{}", s.to_string_lossy())
+    }
+}
+
+/// Deep copy the command using bash's command copying.
+impl Clone for Command {
+    fn clone(&self) -> Self {
+        Command {
+            ptr: unsafe { bash::copy_command(self.ptr) },
         }
     }
 }
@@ -58,7 +246,8 @@ impl FromStr for Command {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let cmd_str = CString::new(s).unwrap();
+        let cmd_str =
+            CString::new(s).map_err(|_| Error::Base("command contains nul byte".into()))?;
         let cmd_ptr = cmd_str.as_ptr() as *mut _;
         let name_ptr = COMMAND_MARKER.as_ptr();
         let cmd: *mut bash::Command;
@@ -69,7 +258,11 @@ impl FromStr for Command {
 
             // parse command from string
             bash::with_input_from_string(cmd_ptr, name_ptr);
-            cmd = match bash::parse_command() {
+            let ret = {
+                let _kind = ScopedErrorKind::new(ErrorKind::Syntax);
+                bash::parse_command()
+            };
+            cmd = match ret {
                 0 => bash::copy_command(bash::GLOBAL_COMMAND),
                 _ => return Err(Error::Base(format!("failed parsing: {s}"))),
             };
@@ -86,20 +279,397 @@ impl FromStr for Command {
     }
 }
 
+/// Values that can be safely interpolated into commands built via [`bash_cmd!`].
+pub trait CommandArg {
+    /// Append the shell words for the value.
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>);
+}
+
+impl CommandArg for str {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        words.push(Cow::Borrowed(OsStr::new(self)));
+    }
+}
+
+impl CommandArg for String {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        self.as_str().push_words(words);
+    }
+}
+
+impl CommandArg for Path {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        words.push(Cow::Borrowed(self.as_os_str()));
+    }
+}
+
+impl CommandArg for PathBuf {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        self.as_path().push_words(words);
+    }
+}
+
+macro_rules! make_numeric_arg {
+    ($($t:ty),*) => {$(
+        impl CommandArg for $t {
+            fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+                words.push(Cow::Owned(self.to_string().into()));
+            }
+        }
+    )*};
+}
+make_numeric_arg!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<T: CommandArg + ?Sized> CommandArg for &T {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        (**self).push_words(words);
+    }
+}
+
+/// Sequences are spliced in as separate words, similar to `"${array[@]}"`.
+impl<T: CommandArg> CommandArg for [T] {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        for value in self {
+            value.push_words(words);
+        }
+    }
+}
+
+impl<T: CommandArg, const N: usize> CommandArg for [T; N] {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        self.as_slice().push_words(words);
+    }
+}
+
+impl<T: CommandArg> CommandArg for Vec<T> {
+    fn push_words<'a>(&'a self, words: &mut Vec<Cow<'a, OsStr>>) {
+        self.as_slice().push_words(words);
+    }
+}
+
+/// Render a value as quoted shell words separated by spaces.
+#[doc(hidden)]
+pub fn quote_words<T: CommandArg + ?Sized>(value: &T) -> crate::Result<String> {
+    let mut words = vec![];
+    value.push_words(&mut words);
+    let words = words
+        .iter()
+        .map(|w| quote::printf_bytes(w.as_bytes()))
+        .collect::<crate::Result<Vec<_>>>()?;
+    Ok(words.join(" "))
+}
+
+/// Verify a [`bash_cmd!`] format string doesn't contain placeholders inside quotes.
+#[doc(hidden)]
+pub fn check_format(fmt: &str) -> crate::Result<()> {
+    let bytes = fmt.as_bytes();
+    let (mut single, mut ansi_c, mut double) = (false, false, false);
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if !single => i += 1,
+            b'\'' if ansi_c => ansi_c = false,
+            b'\'' if !double => single = !single,
+            b'$' if !single && !double && bytes.get(i + 1) == Some(&b'\'') => {
+                ansi_c = true;
+                i += 1;
+            }
+            b'"' if !single && !ansi_c => double = !double,
+            b'{' | b'}' if bytes.get(i + 1) == Some(&bytes[i]) => i += 1,
+            b'{' if single || ansi_c || double => {
+                return Err(Error::Base(format!(
+                    "bash_cmd!: quoted placeholder in format string: {fmt:?}"
+                )))
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Build a [`Command`] from a format string, quoting all interpolated values.
+///
+/// Values are quoted the same way as `printf %q` and only positional `{}` placeholders are
+/// supported, inline captures such as `{name}` fail to compile. Sequences such as slices and
+/// vectors are spliced in as separate words with empty sequences expanding to nothing.
+///
+/// Placeholders must not be inside quotes since the quoted values would be quoted again,
+/// e.g. `"echo '{}'"` returns an error.
+///
+/// ```ignore
+/// let files = ["a b", "c"];
+/// let cmd = bash_cmd!("rm -f {} --msg={}", files, "it's")?;
+/// assert_eq!(cmd.to_string(), r"rm -f a\ b c --msg=it\'s");
+/// ```
+#[macro_export]
+macro_rules! bash_cmd {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {
+        (|| -> $crate::Result<$crate::command::Command> {
+            $crate::command::check_format($fmt)?;
+            // format strings expanded from macros can't capture variables inline
+            let s = format!(concat!($fmt) $(, $crate::command::quote_words(&$arg)?)*);
+            $crate::command::Command::new(s, None)
+        })()
+    };
+}
+pub use bash_cmd;
+
+/// Top-level command parsed from a script along with the lines it spans.
+#[derive(Debug)]
+pub struct ParsedCommand {
+    pub command: Command,
+    /// First line of the command, starting at 1.
+    pub start: usize,
+    /// Last line of the command including any here-document bodies.
+    pub end: usize,
+}
+
+static SCRIPT_MARKER: Lazy<CString> = Lazy::new(|| CString::new("command::parse_script").unwrap());
+
+/// Parse a script into its top-level commands without executing them.
+pub fn parse_script<S: AsRef<str>>(script: S) -> crate::Result<Vec<ParsedCommand>> {
+    parse(script.as_ref(), &SCRIPT_MARKER)
+}
+
+/// Parse a script file into its top-level commands without executing them.
+pub fn parse_script_file<P: AsRef<Path>>(path: P) -> crate::Result<Vec<ParsedCommand>> {
+    let path = path.as_ref();
+    let script = fs::read_to_string(path)
+        .map_err(|e| Error::IO(format!("failed reading file: {path:?}: {e}"), e))?;
+    let name = CString::new(path.to_string_lossy().as_ref()).unwrap();
+    parse(&script, &name)
+}
+
+fn parse(s: &str, name: &CStr) -> crate::Result<Vec<ParsedCommand>> {
+    let mut cmds = vec![];
+    parse_with(s.as_bytes(), name, 1, |cmd| {
+        cmds.push(cmd);
+        Ok(())
+    })?;
+    Ok(cmds)
+}
+
+/// Parse data starting at a given line one top-level command at a time, running a function
+/// on each before parsing the next one.
+///
+/// This allows commands to be executed as they're parsed so changes affecting the parser,
+/// e.g. enabling `extglob`, apply to the following commands as they do when bash sources a
+/// file. Parsing stops at the first error returned by the function.
+pub(crate) fn parse_with<F>(data: &[u8], name: &CStr, line: usize, mut func: F) -> crate::Result<()>
+where
+    F: FnMut(ParsedCommand) -> crate::Result<()>,
+{
+    let _errors = ScopedErrors::new();
+    let script = CString::new(data).map_err(|_| Error::Base("script contains nul byte".into()))?;
+    let script_ptr = script.as_ptr() as *mut _;
+    let mut result = Ok(());
+
+    unsafe {
+        // save input stream, resetting the line number
+        let eof_reached = bash::EOF_REACHED;
+        bash::push_stream(1);
+        bash::with_input_from_string(script_ptr, name.as_ptr());
+        bash::EOF_REACHED = 0;
+        bash::LINE_NUMBER = line.saturating_sub(1) as i32;
+
+        // Each parsed unit ends with a newline or EOF so blank and comment-only lines
+        // result in empty units, meaning each command starts on the line after the
+        // previous unit ended.
+        while bash::EOF_REACHED == 0 {
+            let start = bash::LINE_NUMBER + 1;
+            let ret = {
+                let _kind = ScopedErrorKind::new(ErrorKind::Syntax);
+                bash::parse_command()
+            };
+            if ret != 0 {
+                // bash uses the same exit status for all parser failures
+                result = Err(last_error()
+                    .map(|e| e.or_status(2))
+                    .unwrap_or_else(|| Error::Base(format!("failed parsing: line {start}"))));
+                break;
+            }
+
+            // take ownership of the parsed command
+            let cmd = bash::GLOBAL_COMMAND;
+            bash::GLOBAL_COMMAND = ptr::null_mut();
+            if !cmd.is_null() {
+                let cmd = ParsedCommand {
+                    command: Command { ptr: cmd },
+                    start: start as usize,
+                    end: bash::LINE_NUMBER as usize,
+                };
+
+                // nested evaluation run by the function can alter the EOF status
+                let eof = bash::EOF_REACHED;
+                result = func(cmd);
+                bash::EOF_REACHED = eof;
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+
+        // restore input stream
+        bash::EOF_REACHED = eof_reached;
+        bash::pop_stream();
+    }
+
+    result
+}
+
 /// Get the currently running command name if one exists.
 #[inline]
-pub fn current<'a>() -> Option<&'a str> {
+pub fn current<'a>() -> Option<Cow<'a, str>> {
     unsafe {
         bash::CURRENT_COMMAND
             .as_ref()
-            .map(|s| CStr::from_ptr(s).to_str().unwrap())
+            .map(|s| CStr::from_ptr(s).to_string_lossy())
     }
 }
 
 /// Run a function under a named bash command scope.
 pub(crate) fn cmd_scope<F: FnOnce()>(name: &str, func: F) {
     let name = CString::new(name).unwrap();
+    let _kind = ScopedErrorKind::new(ErrorKind::Builtin);
     unsafe { bash::CURRENT_COMMAND = name.as_ptr() as *mut _ };
     func();
     unsafe { bash::CURRENT_COMMAND = ptr::null_mut() };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let cmd = Command::from_str("echo   a  'b c'  >  file").unwrap();
+        assert_eq!(cmd.to_string(), "echo a 'b c' > file");
+
+        let cmd = Command::from_str("if a; then b; fi").unwrap();
+        assert!(cmd.to_string().starts_with("if a; then\n    b"));
+        assert!(cmd.to_string_indented(2).starts_with("if a; then\n  b"));
+        assert!(cmd.to_string().starts_with("if a; then\n    b"));
+
+        // here-document bodies are left as is
+        let cmd = Command::from_str("if a; then\ncat <<-'EOF'\n    x\n\tEOF\nb\nfi").unwrap();
+        let s = cmd.to_string_indented(2);
+        assert!(s.starts_with("if a; then\n  cat <<-'EOF'\n    x\n\tEOF\n  b"), "{s}");
+
+        // as are multi-line strings and substitutions
+        let cmd = Command::from_str("if a; then\necho 'x\n    y' \"$(\n    z)\"\nb\nfi").unwrap();
+        let s = cmd.to_string_indented(2);
+        assert!(s.contains("'x\n    y'"), "{s}");
+        assert!(s.contains("$(\n    z)"), "{s}");
+        assert!(s.contains("\n  b\n"), "{s}");
+
+        // shift operators aren't here-documents
+        let cmd = Command::from_str("if a; then\n(( x << 1 ))\nb\nfi").unwrap();
+        let s = cmd.to_string_indented(2);
+        assert!(s.contains("\n  b\n"), "{s}");
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let script = [
+            "VAR=1",
+            "",
+            "# comment",
+            "foo() {",
+            "    echo $VAR",
+            "}",
+            "cat <<EOF",
+            "a",
+            "b",
+            "EOF",
+            "echo a \\",
+            "  b",
+        ]
+        .join("\n");
+
+        let cmds = parse_script(&script).unwrap();
+        let lines: Vec<_> = cmds.iter().map(|c| (c.start, c.end)).collect();
+        assert_eq!(lines, [(1, 1), (4, 6), (7, 10), (11, 12)]);
+        assert!(matches!(cmds[1].command.ast().kind, ast::NodeKind::Function { .. }));
+        match cmds[2].command.ast().kind {
+            ast::NodeKind::Simple(cmd) => {
+                assert_eq!(cmd.redirects[0].here_doc_eof.as_deref(), Some("EOF"));
+            }
+            n => panic!("invalid node: {n:?}"),
+        }
+
+        // nothing is executed
+        assert!(crate::functions::find("foo").is_none());
+
+        // empty scripts
+        assert!(parse_script("").unwrap().is_empty());
+        assert!(parse_script("\n# comment\n\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_script_error() {
+        let err = parse_script("a\nb)\n").unwrap_err();
+        assert!(err.to_string().contains("line 2: "));
+        assert!(parse_script("a\0b").is_err());
+    }
+
+    #[test]
+    fn test_parse_script_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "a\nb; c\n\nd").unwrap();
+        let cmds = parse_script_file(file.path()).unwrap();
+        let lines: Vec<_> = cmds.iter().map(|c| (c.start, c.end)).collect();
+        assert_eq!(lines, [(1, 1), (2, 2), (4, 4)]);
+        assert!(parse_script_file("/nonexistent/path").is_err());
+    }
+
+    #[test]
+    fn test_bash_cmd() {
+        let value = "a b; rm -rf /";
+        let cmd = bash_cmd!("echo {} --opt={}", value, "it's").unwrap();
+        assert_eq!(cmd.to_string(), r"echo a\ b\;\ rm\ -rf\ / --opt=it\'s");
+        match cmd.ast().kind {
+            ast::NodeKind::Simple(cmd) => assert_eq!(cmd.words.len(), 3),
+            n => panic!("invalid node: {n:?}"),
+        }
+        assert_eq!(quote::split(cmd.to_string()).unwrap(), ["echo", value, "--opt=it's"]);
+
+        // arrays are spliced as separate words
+        let files = vec!["a", "$(b)", ""];
+        let cmd = bash_cmd!("ls {} {}", files, [1, 2]).unwrap();
+        assert_eq!(quote::split(cmd.to_string()).unwrap(), ["ls", "a", "$(b)", "", "1", "2"]);
+        let empty: [&str; 0] = [];
+        let cmd = bash_cmd!("ls {}", empty).unwrap();
+        assert_eq!(cmd.to_string(), "ls");
+
+        // paths and owned strings
+        let path = Path::new("/tmp/a b");
+        let cmd = bash_cmd!("cat {} {}", path, String::from("`x`")).unwrap();
+        assert_eq!(quote::split(cmd.to_string()).unwrap(), ["cat", "/tmp/a b", "`x`"]);
+
+        // non-UTF-8 paths are quoted using their raw bytes
+        let path = Path::new(OsStr::from_bytes(b"a\xffb"));
+        assert_eq!(quote_words(path).unwrap(), r"$'a\377b'");
+        assert!(bash_cmd!("cat {}", path).is_ok());
+
+        assert!(bash_cmd!("echo {}", "a\0b").is_err());
+
+        // quoted placeholders
+        for fmt in [r#"echo '{}'"#, r#"echo "a {}""#, r#"echo $'\'{}'"#] {
+            assert!(check_format(fmt).is_err(), "{fmt}");
+        }
+        assert!(bash_cmd!("echo '{}'", "a").is_err());
+        assert!(bash_cmd!(r#"echo "\"" {} '{{}}' \'{}"#, "a", "b").is_ok());
+    }
+
+    #[test]
+    fn test_execute_error() {
+        let cmd = Command::from_str("false").unwrap();
+        let err = cmd.execute().unwrap_err();
+        assert_eq!(err.to_string(), "command failed: false");
+    }
+}
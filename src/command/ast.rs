@@ -0,0 +1,727 @@
+use std::ffi::CStr;
+use std::fmt;
+
+use crate::bash;
+use crate::command::Flags;
+
+/// Shell word as returned by the parser, before any expansion is performed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    value: String,
+    flags: i32,
+}
+
+impl Word {
+    /// Convert a raw bash word into its owned equivalent.
+    ///
+    /// # Safety
+    /// The pointer must be null or point to a valid bash word.
+    pub(crate) unsafe fn from_ptr(word: *const bash::WordDesc) -> Option<Self> {
+        unsafe { word.as_ref() }.map(|w| Word {
+            value: unsafe { CStr::from_ptr(w.word).to_string_lossy().into_owned() },
+            flags: w.flags,
+        })
+    }
+
+    /// Return the raw string value of the word.
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Return the raw parser flags for the word.
+    pub fn flags(&self) -> i32 {
+        self.flags
+    }
+
+    /// Returns true if the word is a variable assignment, e.g. `VAR=value`.
+    pub fn is_assignment(&self) -> bool {
+        self.flags as u32 & bash::W_ASSIGNMENT != 0
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl AsRef<str> for Word {
+    fn as_ref(&self) -> &str {
+        &self.value
+    }
+}
+
+impl PartialEq<str> for Word {
+    fn eq(&self, other: &str) -> bool {
+        self.value == other
+    }
+}
+
+impl PartialEq<&str> for Word {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+
+/// Convert a raw bash word list into a vector of owned words.
+///
+/// # Safety
+/// The pointer must be null or point to a valid bash word list.
+pub(crate) unsafe fn words(mut list: *const bash::WordList) -> Vec<Word> {
+    let mut words = vec![];
+    while let Some(l) = unsafe { list.as_ref() } {
+        if let Some(w) = unsafe { Word::from_ptr(l.word) } {
+            words.push(w);
+        }
+        list = l.next;
+    }
+    words
+}
+
+// connectors that aren't parser tokens
+const PIPE: u32 = b'|' as u32;
+const BACKGROUND: u32 = b'&' as u32;
+
+/// Redirection operator types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectOp {
+    /// `>`
+    Output,
+    /// `>|`
+    OutputForce,
+    /// `>>`
+    Append,
+    /// `<`
+    Input,
+    /// `<>`
+    InputOutput,
+    /// `<<`
+    HereDoc,
+    /// `<<-`
+    HereDocStrip,
+    /// `<<<`
+    HereString,
+    /// `<&`
+    DupInput,
+    /// `>&`
+    DupOutput,
+    /// `<&N-`
+    MoveInput,
+    /// `>&N-`
+    MoveOutput,
+    /// `<&-` or `>&-`
+    Close,
+    /// `&>`
+    ErrAndOut,
+    /// `&>>`
+    AppendErrAndOut,
+    /// Raw redirection instruction unsupported by the bindings.
+    Unknown(u32),
+}
+
+impl RedirectOp {
+    fn from_raw(instruction: bash::r_instruction) -> Self {
+        match instruction {
+            bash::r_instruction_r_output_direction => Self::Output,
+            bash::r_instruction_r_output_force => Self::OutputForce,
+            bash::r_instruction_r_appending_to => Self::Append,
+            bash::r_instruction_r_input_direction => Self::Input,
+            bash::r_instruction_r_inputa_direction => Self::Input,
+            bash::r_instruction_r_input_output => Self::InputOutput,
+            bash::r_instruction_r_reading_until => Self::HereDoc,
+            bash::r_instruction_r_deblank_reading_until => Self::HereDocStrip,
+            bash::r_instruction_r_reading_string => Self::HereString,
+            bash::r_instruction_r_duplicating_input => Self::DupInput,
+            bash::r_instruction_r_duplicating_input_word => Self::DupInput,
+            bash::r_instruction_r_duplicating_output => Self::DupOutput,
+            bash::r_instruction_r_duplicating_output_word => Self::DupOutput,
+            bash::r_instruction_r_move_input => Self::MoveInput,
+            bash::r_instruction_r_move_input_word => Self::MoveInput,
+            bash::r_instruction_r_move_output => Self::MoveOutput,
+            bash::r_instruction_r_move_output_word => Self::MoveOutput,
+            bash::r_instruction_r_close_this => Self::Close,
+            bash::r_instruction_r_err_and_out => Self::ErrAndOut,
+            bash::r_instruction_r_append_err_and_out => Self::AppendErrAndOut,
+            n => Self::Unknown(n),
+        }
+    }
+}
+
+/// Source or target of a redirection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectArg {
+    /// File descriptor number.
+    Fd(i32),
+    /// Word, e.g. a file name or `{var}` for redirections that allocate a descriptor.
+    Word(Word),
+}
+
+/// Redirection attached to a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub source: RedirectArg,
+    pub op: RedirectOp,
+    pub target: RedirectArg,
+    /// Delimiter for here-documents, the target contains the document body.
+    pub here_doc_eof: Option<String>,
+}
+
+impl Redirect {
+    /// Convert a raw bash redirection list into a vector of owned redirections.
+    ///
+    /// # Safety
+    /// The pointer must be null or point to a valid bash redirection list.
+    pub(crate) unsafe fn list(mut redirect: *const bash::Redirect) -> Vec<Self> {
+        let mut redirects = vec![];
+        while let Some(r) = unsafe { redirect.as_ref() } {
+            redirects.push(unsafe { Redirect::from_raw(r) });
+            redirect = r.next;
+        }
+        redirects
+    }
+
+    unsafe fn from_raw(r: &bash::Redirect) -> Self {
+        let word = |w| unsafe { Word::from_ptr(w) }.map(RedirectArg::Word);
+
+        let source = match r.rflags as u32 & bash::REDIR_VARASSIGN {
+            0 => RedirectArg::Fd(unsafe { r.redirector.dest }),
+            _ => word(unsafe { r.redirector.filename }).unwrap_or(RedirectArg::Fd(-1)),
+        };
+
+        let target = match r.instruction {
+            bash::r_instruction_r_duplicating_input
+            | bash::r_instruction_r_duplicating_output
+            | bash::r_instruction_r_move_input
+            | bash::r_instruction_r_move_output
+            | bash::r_instruction_r_close_this => RedirectArg::Fd(unsafe { r.redirectee.dest }),
+            _ => word(unsafe { r.redirectee.filename }).unwrap_or(RedirectArg::Fd(-1)),
+        };
+
+        let here_doc_eof = unsafe { r.here_doc_eof.as_ref() }
+            .map(|s| unsafe { CStr::from_ptr(s).to_string_lossy().into_owned() });
+
+        Redirect {
+            source,
+            op: RedirectOp::from_raw(r.instruction),
+            target,
+            here_doc_eof,
+        }
+    }
+}
+
+/// Simple command consisting of words and redirections, e.g. `VAR=1 cmd arg >file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simple {
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+impl Simple {
+    /// Return the leading variable assignments.
+    pub fn assignments(&self) -> impl Iterator<Item = &Word> {
+        self.words.iter().take_while(|w| w.is_assignment())
+    }
+
+    /// Return the command name if one exists.
+    pub fn name(&self) -> Option<&Word> {
+        self.words.iter().find(|w| !w.is_assignment())
+    }
+
+    /// Return the words following the command name.
+    pub fn args(&self) -> &[Word] {
+        match self.words.iter().position(|w| !w.is_assignment()) {
+            Some(i) => &self.words[i + 1..],
+            None => &[],
+        }
+    }
+}
+
+/// Terminator used for a `case` clause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseTerminator {
+    /// `;;`
+    Break,
+    /// `;&`
+    FallThrough,
+    /// `;;&`
+    TestNext,
+}
+
+/// Single pattern clause of a `case` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseClause {
+    pub patterns: Vec<Word>,
+    pub body: Option<Node>,
+    pub terminator: CaseTerminator,
+}
+
+/// Conditional expression used with `[[ ]]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CondExpr {
+    And(Box<CondExpr>, Box<CondExpr>),
+    Or(Box<CondExpr>, Box<CondExpr>),
+    Not(Box<CondExpr>),
+    Group(Box<CondExpr>),
+    Unary { op: Word, arg: Word },
+    Binary { op: Word, left: Word, right: Word },
+    Term(Word),
+}
+
+impl CondExpr {
+    unsafe fn from_ptr(cond: *const bash::CondCom) -> Option<Self> {
+        let c = unsafe { cond.as_ref() }?;
+        let term =
+            |p: *const bash::CondCom| unsafe { p.as_ref().and_then(|c| Word::from_ptr(c.op)) };
+        let expr = |p| unsafe { CondExpr::from_ptr(p) }.map(Box::new);
+        let op = unsafe { Word::from_ptr(c.op) };
+
+        let expr = match c.type_ as u32 {
+            bash::COND_AND => CondExpr::And(expr(c.left)?, expr(c.right)?),
+            bash::COND_OR => CondExpr::Or(expr(c.left)?, expr(c.right)?),
+            bash::COND_EXPR => CondExpr::Group(expr(c.left)?),
+            bash::COND_UNARY => CondExpr::Unary {
+                op: op?,
+                arg: term(c.left)?,
+            },
+            bash::COND_BINARY => CondExpr::Binary {
+                op: op?,
+                left: term(c.left)?,
+                right: term(c.right)?,
+            },
+            _ => CondExpr::Term(op?),
+        };
+
+        match c.flags as u32 & Flags::INVERT_RETURN.bits() {
+            0 => Some(expr),
+            _ => Some(CondExpr::Not(Box::new(expr))),
+        }
+    }
+}
+
+/// Type-specific data for a parsed command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    /// `cmd arg1 arg2`
+    Simple(Simple),
+    /// `cmd1 | cmd2 | cmd3`
+    Pipeline(Vec<Node>),
+    /// `cmd1 && cmd2`
+    And(Box<Node>, Box<Node>),
+    /// `cmd1 || cmd2`
+    Or(Box<Node>, Box<Node>),
+    /// `cmd1; cmd2; cmd3`
+    List(Vec<Node>),
+    /// `cmd &`
+    Background(Box<Node>),
+    /// `if test; then body; else body; fi`
+    If {
+        test: Box<Node>,
+        then: Box<Node>,
+        r#else: Option<Box<Node>>,
+    },
+    /// `while test; do body; done`
+    While { test: Box<Node>, body: Box<Node> },
+    /// `until test; do body; done`
+    Until { test: Box<Node>, body: Box<Node> },
+    /// `for var in words; do body; done`
+    For {
+        var: Word,
+        words: Vec<Word>,
+        body: Box<Node>,
+    },
+    /// `for (( init; test; step )); do body; done`
+    ArithFor {
+        init: Vec<Word>,
+        test: Vec<Word>,
+        step: Vec<Word>,
+        body: Box<Node>,
+    },
+    /// `select var in words; do body; done`
+    Select {
+        var: Word,
+        words: Vec<Word>,
+        body: Box<Node>,
+    },
+    /// `case word in pattern) body;; esac`
+    Case {
+        word: Word,
+        clauses: Vec<CaseClause>,
+    },
+    /// `name() { body; }`
+    Function {
+        name: Word,
+        body: Box<Node>,
+        source_file: Option<String>,
+    },
+    /// `{ body; }`
+    Group(Box<Node>),
+    /// `( body )`
+    Subshell(Box<Node>),
+    /// `coproc name { body; }`
+    Coproc { name: String, body: Box<Node> },
+    /// `(( expr ))`
+    Arith(Vec<Word>),
+    /// `[[ expr ]]`
+    Cond(CondExpr),
+    /// Raw command type that is unsupported by the bindings or malformed.
+    Unknown(u32),
+}
+
+/// Parsed command tree node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub line: i32,
+    pub flags: Flags,
+    /// Redirections applied to compound commands, simple command redirections are stored
+    /// with their related command.
+    pub redirects: Vec<Redirect>,
+}
+
+impl Node {
+    /// Convert a raw bash command into its owned, typed equivalent.
+    ///
+    /// # Safety
+    /// The pointer must be null or point to a valid bash command.
+    pub(crate) unsafe fn from_ptr(cmd: *const bash::Command) -> Option<Self> {
+        unsafe { cmd.as_ref() }.map(|c| unsafe { Node::from_raw(c) })
+    }
+
+    /// Convert a raw bash command, falling back to [`NodeKind::Unknown`] for unsupported or
+    /// malformed commands.
+    ///
+    /// # Safety
+    /// The command must be valid.
+    pub(crate) unsafe fn from_raw(c: &bash::Command) -> Self {
+        let mut line = c.line;
+        let kind = unsafe { Node::kind(c, &mut line) }.unwrap_or(NodeKind::Unknown(c.type_));
+        let node = Node {
+            kind,
+            line,
+            flags: Flags::from_bits_truncate(c.flags as u32),
+            redirects: unsafe { Redirect::list(c.redirects) },
+        };
+
+        // unwrap commands with a trailing `;` or newline that isn't otherwise modified
+        match node.kind {
+            NodeKind::List(mut nodes)
+                if nodes.len() == 1 && node.flags.is_empty() && node.redirects.is_empty() =>
+            {
+                nodes.remove(0)
+            }
+            kind => Node { kind, ..node },
+        }
+    }
+
+    unsafe fn kind(c: &bash::Command, line: &mut i32) -> Option<NodeKind> {
+        let node = |p| unsafe { Node::from_ptr(p) }.map(Box::new);
+
+        let kind = unsafe {
+            match c.type_ {
+                bash::command_type_cm_simple => {
+                    let s = &*c.value.Simple;
+                    *line = s.line;
+                    NodeKind::Simple(Simple {
+                        words: words(s.words),
+                        redirects: Redirect::list(s.redirects),
+                    })
+                }
+                bash::command_type_cm_connection => {
+                    let conn = &*c.value.Connection;
+                    let first = Node::from_ptr(conn.first)?;
+                    let second = Node::from_ptr(conn.second);
+                    *line = first.line;
+                    match (conn.connector as u32, second) {
+                        (bash::AND_AND, Some(second)) => {
+                            NodeKind::And(Box::new(first), Box::new(second))
+                        }
+                        (bash::OR_OR, Some(second)) => {
+                            NodeKind::Or(Box::new(first), Box::new(second))
+                        }
+                        (PIPE, second) => {
+                            let mut nodes = first.flatten(true);
+                            nodes.extend(second.map(|n| n.flatten(true)).unwrap_or_default());
+                            NodeKind::Pipeline(nodes)
+                        }
+                        (BACKGROUND, None) => NodeKind::Background(Box::new(first)),
+                        (BACKGROUND, Some(second)) => {
+                            let bg = Node {
+                                kind: NodeKind::Background(Box::new(first)),
+                                line: *line,
+                                flags: Flags::NONE,
+                                redirects: vec![],
+                            };
+                            let mut nodes = vec![bg];
+                            nodes.extend(second.flatten(false));
+                            NodeKind::List(nodes)
+                        }
+                        (_, None) => NodeKind::List(vec![first]),
+                        (_, Some(second)) => {
+                            let mut nodes = first.flatten(false);
+                            nodes.extend(second.flatten(false));
+                            NodeKind::List(nodes)
+                        }
+                    }
+                }
+                bash::command_type_cm_if => {
+                    let i = &*c.value.If;
+                    NodeKind::If {
+                        test: node(i.test)?,
+                        then: node(i.true_case)?,
+                        r#else: node(i.false_case),
+                    }
+                }
+                bash::command_type_cm_while => {
+                    let w = &*c.value.While;
+                    NodeKind::While {
+                        test: node(w.test)?,
+                        body: node(w.action)?,
+                    }
+                }
+                bash::command_type_cm_until => {
+                    let w = &*c.value.While;
+                    NodeKind::Until {
+                        test: node(w.test)?,
+                        body: node(w.action)?,
+                    }
+                }
+                bash::command_type_cm_for => {
+                    let f = &*c.value.For;
+                    *line = f.line;
+                    NodeKind::For {
+                        var: Word::from_ptr(f.name)?,
+                        words: words(f.map_list),
+                        body: node(f.action)?,
+                    }
+                }
+                bash::command_type_cm_arith_for => {
+                    let f = &*c.value.ArithFor;
+                    *line = f.line;
+                    NodeKind::ArithFor {
+                        init: words(f.init),
+                        test: words(f.test),
+                        step: words(f.step),
+                        body: node(f.action)?,
+                    }
+                }
+                bash::command_type_cm_select => {
+                    let s = &*c.value.Select;
+                    *line = s.line;
+                    NodeKind::Select {
+                        var: Word::from_ptr(s.name)?,
+                        words: words(s.map_list),
+                        body: node(s.action)?,
+                    }
+                }
+                bash::command_type_cm_case => {
+                    let cc = &*c.value.Case;
+                    *line = cc.line;
+                    let mut clauses = vec![];
+                    let mut clause = cc.clauses as *const bash::PatternList;
+                    while let Some(p) = clause.as_ref() {
+                        let terminator = match p.flags as u32 {
+                            f if f & bash::CASEPAT_FALLTHROUGH != 0 => CaseTerminator::FallThrough,
+                            f if f & bash::CASEPAT_TESTNEXT != 0 => CaseTerminator::TestNext,
+                            _ => CaseTerminator::Break,
+                        };
+                        clauses.push(CaseClause {
+                            patterns: words(p.patterns),
+                            body: Node::from_ptr(p.action),
+                            terminator,
+                        });
+                        clause = p.next;
+                    }
+                    NodeKind::Case {
+                        word: Word::from_ptr(cc.word)?,
+                        clauses,
+                    }
+                }
+                bash::command_type_cm_function_def => {
+                    let f = &*c.value.Function_def;
+                    *line = f.line;
+                    NodeKind::Function {
+                        name: Word::from_ptr(f.name)?,
+                        body: node(f.command)?,
+                        source_file: f
+                            .source_file
+                            .as_ref()
+                            .map(|s| CStr::from_ptr(s).to_string_lossy().into_owned()),
+                    }
+                }
+                bash::command_type_cm_group => NodeKind::Group(node((*c.value.Group).command)?),
+                bash::command_type_cm_subshell => {
+                    let s = &*c.value.Subshell;
+                    *line = s.line;
+                    NodeKind::Subshell(node(s.command)?)
+                }
+                bash::command_type_cm_coproc => {
+                    let cp = &*c.value.Coproc;
+                    NodeKind::Coproc {
+                        name: CStr::from_ptr(cp.name).to_string_lossy().into_owned(),
+                        body: node(cp.command)?,
+                    }
+                }
+                bash::command_type_cm_arith => {
+                    let a = &*c.value.Arith;
+                    *line = a.line;
+                    NodeKind::Arith(words(a.exp))
+                }
+                bash::command_type_cm_cond => {
+                    let cond = c.value.Cond;
+                    *line = (*cond).line;
+                    NodeKind::Cond(CondExpr::from_ptr(cond)?)
+                }
+                n => NodeKind::Unknown(n),
+            }
+        };
+
+        Some(kind)
+    }
+
+    /// Unwrap nested, unmodified nodes of the same connection type into a single list.
+    fn flatten(self, pipeline: bool) -> Vec<Node> {
+        let plain = self.flags.is_empty() && self.redirects.is_empty();
+        match self.kind {
+            NodeKind::Pipeline(nodes) if pipeline && plain => nodes,
+            NodeKind::List(nodes) if !pipeline && plain => nodes,
+            kind => vec![Node { kind, ..self }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::command::Command;
+
+    use super::*;
+
+    fn parse(s: &str) -> NodeKind {
+        Command::from_str(s).unwrap().ast().kind
+    }
+
+    #[test]
+    fn test_simple() {
+        let cmd = match parse("VAR=1 echo a b >file 2>&1") {
+            NodeKind::Simple(cmd) => cmd,
+            n => panic!("invalid node: {n:?}"),
+        };
+        assert_eq!(cmd.words, ["VAR=1", "echo", "a", "b"]);
+        assert_eq!(cmd.assignments().collect::<Vec<_>>(), ["VAR=1"]);
+        assert_eq!(cmd.name().unwrap(), "echo");
+        assert_eq!(cmd.args(), ["a", "b"]);
+        assert_eq!(cmd.redirects.len(), 2);
+        assert_eq!(cmd.redirects[0].source, RedirectArg::Fd(1));
+        assert_eq!(cmd.redirects[0].op, RedirectOp::Output);
+        assert!(matches!(&cmd.redirects[0].target, RedirectArg::Word(w) if w == "file"));
+        assert_eq!(cmd.redirects[1].source, RedirectArg::Fd(2));
+        assert_eq!(cmd.redirects[1].op, RedirectOp::DupOutput);
+        assert_eq!(cmd.redirects[1].target, RedirectArg::Fd(1));
+    }
+
+    #[test]
+    fn test_connections() {
+        assert!(matches!(parse("a | b | c"), NodeKind::Pipeline(nodes) if nodes.len() == 3));
+
+        match parse("a && b || c") {
+            NodeKind::Or(left, _) => assert!(matches!(left.kind, NodeKind::And(_, _))),
+            n => panic!("invalid node: {n:?}"),
+        }
+
+        match parse("a; b & c") {
+            NodeKind::List(nodes) => {
+                assert_eq!(nodes.len(), 3);
+                assert!(matches!(nodes[1].kind, NodeKind::Background(_)));
+            }
+            n => panic!("invalid node: {n:?}"),
+        }
+
+        let node = Command::from_str("! a | b").unwrap().ast();
+        assert!(node.flags.contains(Flags::INVERT_RETURN));
+    }
+
+    #[test]
+    fn test_compound() {
+        assert!(matches!(
+            parse("if a; then b; else c; fi"),
+            NodeKind::If {
+                r#else: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(parse("while a; do b; done"), NodeKind::While { .. }));
+        assert!(matches!(parse("until a; do b; done"), NodeKind::Until { .. }));
+        assert!(matches!(parse("for ((i=0; i<3; i++)); do :; done"), NodeKind::ArithFor { .. }));
+        assert!(matches!(parse("select x in a b; do :; done"), NodeKind::Select { .. }));
+        assert!(matches!(parse("(a; b)"), NodeKind::Subshell(_)));
+
+        match parse("for x in 1 2; do echo $x; done") {
+            NodeKind::For { var, words, .. } => {
+                assert_eq!(var, "x");
+                assert_eq!(words, ["1", "2"]);
+            }
+            n => panic!("invalid node: {n:?}"),
+        }
+
+        match parse("case $x in a|b) :;; c) :;& *) :;;& esac") {
+            NodeKind::Case { word, clauses } => {
+                assert_eq!(word, "$x");
+                assert_eq!(clauses.len(), 3);
+                assert_eq!(clauses[0].patterns, ["a", "b"]);
+                assert_eq!(clauses[0].terminator, CaseTerminator::Break);
+                assert_eq!(clauses[1].terminator, CaseTerminator::FallThrough);
+                assert_eq!(clauses[2].terminator, CaseTerminator::TestNext);
+            }
+            n => panic!("invalid node: {n:?}"),
+        }
+
+        let node = Command::from_str("{ a; b; } >file").unwrap().ast();
+        assert!(matches!(node.kind, NodeKind::Group(_)));
+        assert_eq!(node.redirects.len(), 1);
+    }
+
+    #[test]
+    fn test_function() {
+        match parse("foo() { echo bar; }") {
+            NodeKind::Function { name, body, .. } => {
+                assert_eq!(name, "foo");
+                assert!(matches!(body.kind, NodeKind::Group(_)));
+            }
+            n => panic!("invalid node: {n:?}"),
+        }
+    }
+
+    #[test]
+    fn test_arith_and_cond() {
+        assert!(
+            matches!(parse("(( x += 1 ))"), NodeKind::Arith(w) if w[0].as_str().trim() == "x += 1")
+        );
+
+        let (left, right) = match parse("[[ -n $x && ! ( $y == z ) ]]") {
+            NodeKind::Cond(CondExpr::And(left, right)) => (left, right),
+            n => panic!("invalid node: {n:?}"),
+        };
+        assert!(matches!(*left, CondExpr::Unary { op, arg } if op == "-n" && arg == "$x"));
+        match *right {
+            CondExpr::Not(expr) => match *expr {
+                CondExpr::Group(expr) => {
+                    assert!(matches!(*expr, CondExpr::Binary { op, .. } if op == "=="))
+                }
+                e => panic!("invalid expr: {e:?}"),
+            },
+            e => panic!("invalid expr: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(RedirectOp::from_raw(1000), RedirectOp::Unknown(1000));
+
+        let mut cmd: bash::Command = unsafe { std::mem::zeroed() };
+        cmd.type_ = 1000;
+        let node = unsafe { Node::from_raw(&cmd) };
+        assert_eq!(node.kind, NodeKind::Unknown(1000));
+        assert!(node.redirects.is_empty());
+    }
+}
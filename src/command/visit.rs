@@ -0,0 +1,263 @@
+use crate::command::ast::*;
+use crate::command::ParsedCommand;
+
+/// Callbacks for traversing a parsed command tree.
+///
+/// Each method defaults to walking its children so implementations only need to override
+/// the callbacks they're interested in, calling the related `walk_*` function to continue
+/// descending into the tree if desired. The `line` parameter is the line of the closest
+/// enclosing command.
+pub trait Visitor {
+    /// Visit a command node of any type.
+    fn visit_node(&mut self, node: &Node) {
+        walk_node(self, node);
+    }
+
+    /// Visit a simple command.
+    fn visit_simple(&mut self, cmd: &Simple, line: i32) {
+        walk_simple(self, cmd, line);
+    }
+
+    /// Visit a word, e.g. a command name, argument, loop list item, or redirection target.
+    fn visit_word(&mut self, _word: &Word, _line: i32) {}
+
+    /// Visit a variable assignment preceding a simple command.
+    fn visit_assignment(&mut self, _word: &Word, _line: i32) {}
+
+    /// Visit a redirection.
+    fn visit_redirect(&mut self, redirect: &Redirect, line: i32) {
+        walk_redirect(self, redirect, line);
+    }
+
+    /// Visit a function definition.
+    fn visit_function(&mut self, _name: &Word, body: &Node, _line: i32) {
+        self.visit_node(body);
+    }
+
+    /// Visit a control structure or other compound command, e.g. `if`, `while`, `for`,
+    /// `case`, `[[ ]]`, or a subshell.
+    fn visit_compound(&mut self, node: &Node) {
+        walk_compound(self, node);
+    }
+
+    /// Visit a conditional expression used with `[[ ]]`.
+    fn visit_cond(&mut self, expr: &CondExpr, line: i32) {
+        walk_cond(self, expr, line);
+    }
+}
+
+/// Visit all the top-level commands of a parsed script in order.
+pub fn visit_script<V: Visitor + ?Sized>(visitor: &mut V, cmds: &[ParsedCommand]) {
+    for cmd in cmds {
+        visitor.visit_node(&cmd.command.ast());
+    }
+}
+
+/// Dispatch a node to its related callbacks.
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    match &node.kind {
+        NodeKind::Simple(cmd) => visitor.visit_simple(cmd, node.line),
+        NodeKind::Pipeline(nodes) | NodeKind::List(nodes) => {
+            for n in nodes {
+                visitor.visit_node(n);
+            }
+        }
+        NodeKind::And(left, right) | NodeKind::Or(left, right) => {
+            visitor.visit_node(left);
+            visitor.visit_node(right);
+        }
+        NodeKind::Background(n) => visitor.visit_node(n),
+        NodeKind::Function { name, body, .. } => visitor.visit_function(name, body, node.line),
+        _ => visitor.visit_compound(node),
+    }
+
+    for r in &node.redirects {
+        visitor.visit_redirect(r, node.line);
+    }
+}
+
+/// Visit the assignments, words, and redirections of a simple command.
+pub fn walk_simple<V: Visitor + ?Sized>(visitor: &mut V, cmd: &Simple, line: i32) {
+    let mut words = cmd.words.iter().peekable();
+    while let Some(w) = words.next_if(|w| w.is_assignment()) {
+        visitor.visit_assignment(w, line);
+    }
+    for w in words {
+        visitor.visit_word(w, line);
+    }
+    for r in &cmd.redirects {
+        visitor.visit_redirect(r, line);
+    }
+}
+
+/// Visit the word targeted by a redirection if one exists.
+pub fn walk_redirect<V: Visitor + ?Sized>(visitor: &mut V, redirect: &Redirect, line: i32) {
+    if let RedirectArg::Word(w) = &redirect.target {
+        visitor.visit_word(w, line);
+    }
+}
+
+/// Visit the words and nested commands of a compound command.
+pub fn walk_compound<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    let line = node.line;
+    let words = |visitor: &mut V, words: &[Word]| {
+        for w in words {
+            visitor.visit_word(w, line);
+        }
+    };
+
+    match &node.kind {
+        NodeKind::If { test, then, r#else } => {
+            visitor.visit_node(test);
+            visitor.visit_node(then);
+            if let Some(n) = r#else {
+                visitor.visit_node(n);
+            }
+        }
+        NodeKind::While { test, body } | NodeKind::Until { test, body } => {
+            visitor.visit_node(test);
+            visitor.visit_node(body);
+        }
+        NodeKind::For {
+            words: list, body, ..
+        }
+        | NodeKind::Select {
+            words: list, body, ..
+        } => {
+            words(visitor, list);
+            visitor.visit_node(body);
+        }
+        NodeKind::ArithFor {
+            init,
+            test,
+            step,
+            body,
+        } => {
+            words(visitor, init);
+            words(visitor, test);
+            words(visitor, step);
+            visitor.visit_node(body);
+        }
+        NodeKind::Case { word, clauses } => {
+            visitor.visit_word(word, line);
+            for clause in clauses {
+                words(visitor, &clause.patterns);
+                if let Some(n) = &clause.body {
+                    visitor.visit_node(n);
+                }
+            }
+        }
+        NodeKind::Group(n) | NodeKind::Subshell(n) | NodeKind::Coproc { body: n, .. } => {
+            visitor.visit_node(n);
+        }
+        NodeKind::Arith(exprs) => words(visitor, exprs),
+        NodeKind::Cond(expr) => visitor.visit_cond(expr, line),
+        NodeKind::Unknown(_) => (),
+        _ => walk_node(visitor, node),
+    }
+}
+
+/// Visit the operands of a conditional expression.
+pub fn walk_cond<V: Visitor + ?Sized>(visitor: &mut V, expr: &CondExpr, line: i32) {
+    match expr {
+        CondExpr::And(left, right) | CondExpr::Or(left, right) => {
+            visitor.visit_cond(left, line);
+            visitor.visit_cond(right, line);
+        }
+        CondExpr::Not(expr) | CondExpr::Group(expr) => visitor.visit_cond(expr, line),
+        CondExpr::Unary { arg, .. } => visitor.visit_word(arg, line),
+        CondExpr::Binary { left, right, .. } => {
+            visitor.visit_word(left, line);
+            visitor.visit_word(right, line);
+        }
+        CondExpr::Term(word) => visitor.visit_word(word, line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::parse_script;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Collector {
+        commands: Vec<(String, i32)>,
+        functions: Vec<String>,
+        assignments: Vec<String>,
+        words: Vec<String>,
+        compound: usize,
+    }
+
+    impl Visitor for Collector {
+        fn visit_simple(&mut self, cmd: &Simple, line: i32) {
+            if let Some(name) = cmd.name() {
+                self.commands.push((name.to_string(), line));
+            }
+            walk_simple(self, cmd, line);
+        }
+
+        fn visit_word(&mut self, word: &Word, _line: i32) {
+            self.words.push(word.to_string());
+        }
+
+        fn visit_assignment(&mut self, word: &Word, _line: i32) {
+            self.assignments.push(word.to_string());
+        }
+
+        fn visit_function(&mut self, name: &Word, body: &Node, _line: i32) {
+            self.functions.push(name.to_string());
+            self.visit_node(body);
+        }
+
+        fn visit_compound(&mut self, node: &Node) {
+            self.compound += 1;
+            walk_compound(self, node);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let script = [
+            "VAR=1 foo a",
+            "bar() {",
+            "    if [[ -n $x ]]; then",
+            "        baz $D >file",
+            "    fi",
+            "}",
+            "for x in 1 2; do qux | quux; done",
+        ]
+        .join("\n");
+        let cmds = parse_script(script).unwrap();
+        let mut v = Collector::default();
+        visit_script(&mut v, &cmds);
+
+        let names: Vec<_> = v.commands.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(names, ["foo", "baz", "qux", "quux"]);
+        assert_eq!(v.commands[0].1, 1);
+        assert_eq!(v.commands[1].1, 4);
+        assert_eq!(v.functions, ["bar"]);
+        assert_eq!(v.assignments, ["VAR=1"]);
+        assert_eq!(v.words, ["foo", "a", "$x", "baz", "$D", "file", "1", "2", "qux", "quux"]);
+        // function body group, if, [[ ]], for, and loop body group
+        assert!(v.compound >= 4);
+    }
+
+    #[test]
+    fn test_banned_commands() {
+        struct Banned(Vec<i32>);
+
+        impl Visitor for Banned {
+            fn visit_simple(&mut self, cmd: &Simple, line: i32) {
+                if cmd.name().map(|w| w == "eapply").unwrap_or_default() {
+                    self.0.push(line);
+                }
+            }
+        }
+
+        let cmds = parse_script("a\nif b; then\n  eapply x\nfi\neapply y").unwrap();
+        let mut v = Banned(vec![]);
+        visit_script(&mut v, &cmds);
+        assert_eq!(v.0, [3, 5]);
+    }
+}
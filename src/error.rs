@@ -1,55 +1,444 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ffi::CStr;
-use std::io;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_int};
+use std::{fmt, io};
 
+use nix::libc;
 use tracing::warn;
 
-use crate::builtins::ExecStatus;
-use crate::shell::Shell;
+use crate::builtins::{builtin_level, ExecStatus};
+use crate::shell::in_subshell;
+use crate::variables::array_to_vec;
+use crate::{bash, command};
+
+pub(crate) mod channel;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, Clone, thiserror::Error)]
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("{0}")]
     Bail(String),
     #[error("{0}")]
     Base(String),
-    #[error("{1}")]
-    IO(io::ErrorKind, String),
+    #[error("{0}")]
+    IO(String, #[source] io::Error),
     #[error("{1}")]
     Status(ExecStatus, String),
+    #[error("{0}")]
+    Bash(BashError),
+}
+
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        match self {
+            Error::Bail(s) => Error::Bail(s.clone()),
+            Error::Base(s) => Error::Base(s.clone()),
+            Error::IO(s, e) => {
+                // io::Error isn't cloneable so recreate it, preserving the errno if one exists
+                let e = match e.raw_os_error() {
+                    Some(errno) => io::Error::from_raw_os_error(errno),
+                    None => io::Error::new(e.kind(), e.to_string()),
+                };
+                Error::IO(s.clone(), e)
+            }
+            Error::Status(status, s) => Error::Status(*status, s.clone()),
+            Error::Bash(e) => Error::Bash(e.clone()),
+        }
+    }
+}
+
+impl Error {
+    /// Return the exit status related to the error if one is known.
+    pub fn status(&self) -> Option<ExecStatus> {
+        match self {
+            Error::Status(status, _) => Some(*status),
+            Error::Bash(e) => e.status,
+            _ => None,
+        }
+    }
+
+    /// Set the exit status of a bash error that doesn't have one using the nonzero return
+    /// value of the call that raised it.
+    pub(crate) fn or_status(mut self, ret: c_int) -> Self {
+        if let Error::Bash(e) = &mut self {
+            if e.status.is_none() && ret != 0 {
+                e.status = Some(ExecStatus::Failure(ret));
+            }
+        }
+        self
+    }
+
+    /// Return the kind of error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::IO(..) => ErrorKind::Io,
+            Error::Bash(e) => e.kind,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Category of a bash error determined by the code path that raised it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Error raised while parsing.
+    Syntax,
+    /// Assigning to or unsetting a readonly variable.
+    Readonly,
+    /// Error raised by a running builtin, e.g. invalid options or arguments.
+    Builtin,
+    /// System error with a related errno value.
+    Io,
+    /// Warning converted into an error.
+    Warning,
+    /// Error raised from an unclassified code path.
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Syntax => "syntax error",
+            Self::Readonly => "readonly variable",
+            Self::Builtin => "builtin error",
+            Self::Io => "IO error",
+            Self::Warning => "warning",
+            Self::Other => "error",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Error kind for bash errors raised during its lifetime, restoring the previous kind on drop.
+pub(crate) struct ScopedErrorKind {
+    orig: Option<ErrorKind>,
+}
+
+impl ScopedErrorKind {
+    pub(crate) fn new(kind: ErrorKind) -> Self {
+        Self {
+            orig: ERROR_KIND.with(|k| k.replace(Some(kind))),
+        }
+    }
+}
+
+impl Drop for ScopedErrorKind {
+    fn drop(&mut self) {
+        ERROR_KIND.with(|k| k.set(self.orig));
+    }
+}
+
+/// Error raised by bash split into its components.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BashError {
+    /// Source file the error occurred in if one exists.
+    pub file: Option<String>,
+    /// Line the error occurred on if one exists.
+    pub line: Option<usize>,
+    /// Category of the error.
+    pub kind: ErrorKind,
+    /// Builtin or command that raised the error if one exists.
+    pub command: Option<String>,
+    /// Exit status of the failing command if it's known.
+    pub status: Option<ExecStatus>,
+    /// Full error message as output by bash.
+    pub message: String,
+}
+
+impl BashError {
+    /// Create an error for a bash error message that is currently being raised, determining
+    /// its location from bash's state.
+    pub(crate) fn new(kind: ErrorKind, message: &str) -> Self {
+        let (file, line) = location(kind);
+        let rest = strip_location(message, file.as_deref(), line);
+
+        // errors from the running command are prefixed with its name
+        let command = command::current()
+            .filter(|name| rest.starts_with(&format!("{name}: ")))
+            .map(|s| s.into_owned());
+
+        Self {
+            file,
+            line,
+            kind,
+            command,
+            // set by the caller from the return value of the failing call
+            status: None,
+            message: message.to_string(),
+        }
+    }
+
+    /// Return the error description without its location and command prefixes.
+    pub fn description(&self) -> &str {
+        let mut s = strip_location(&self.message, self.file.as_deref(), self.line);
+        if let Some(cmd) = &self.command {
+            s = s
+                .strip_prefix(cmd.as_str())
+                .and_then(|s| s.strip_prefix(": "))
+                .unwrap_or(s);
+        }
+        s
+    }
+}
+
+impl fmt::Display for BashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Determine the file and line for an error or warning currently being raised, using the
+/// same bash state bash uses to prefix its messages.
+pub(crate) fn location(kind: ErrorKind) -> (Option<String>, Option<usize>) {
+    let (file, line) = match kind {
+        // parser errors and warnings, e.g. unterminated here-documents, use the input being
+        // parsed
+        ErrorKind::Syntax | ErrorKind::Warning => unsafe {
+            let name = CStr::from_ptr(bash::yy_input_name()).to_string_lossy();
+            (Some(name.into_owned()), bash::LINE_NUMBER)
+        },
+        // runtime errors use the innermost source file, unset for unnamed strings
+        _ => {
+            let file = array_to_vec("BASH_SOURCE")
+                .ok()
+                .and_then(|files| files.into_iter().next())
+                .filter(|s| !s.is_empty());
+            (file, unsafe { bash::executing_line_number() })
+        }
+    };
+
+    match line {
+        n if n > 0 => (file, Some(n as usize)),
+        _ => (None, None),
+    }
+}
+
+/// Strip the location prefix bash adds to messages, e.g. `file: line 1: `.
+fn strip_location<'a>(message: &'a str, file: Option<&str>, line: Option<usize>) -> &'a str {
+    let prefix = match (file, line) {
+        (Some(file), Some(line)) => format!("{file}: line {line}: "),
+        (None, Some(line)) => format!("line {line}: "),
+        _ => return message,
+    };
+    message.strip_prefix(&prefix).unwrap_or(message)
+}
+
+/// Warning raised by bash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Source file the warning occurred in if one exists.
+    pub file: Option<String>,
+    /// Line the warning occurred on if one exists.
+    pub line: Option<usize>,
+    /// Full warning message as output by bash.
+    pub message: String,
+}
+
+impl Warning {
+    fn new(message: &str) -> Self {
+        let (file, line) = location(ErrorKind::Warning);
+        Self {
+            file,
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    /// Return the warning description without its location and warning prefixes.
+    pub fn description(&self) -> &str {
+        let s = strip_location(&self.message, self.file.as_deref(), self.line);
+        s.strip_prefix("warning: ").unwrap_or(s)
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<Warning> for Error {
+    fn from(w: Warning) -> Self {
+        Error::Bash(BashError {
+            file: w.file,
+            line: w.line,
+            kind: ErrorKind::Warning,
+            command: None,
+            status: None,
+            message: w.message,
+        })
+    }
 }
 
 thread_local! {
-    static LAST_ERROR: RefCell<Option<Error>> = RefCell::new(None);
+    // most recent error for each nested error scope, innermost last
+    static ERRORS: RefCell<Vec<Option<Error>>> = RefCell::new(vec![None]);
+    // error kind set by the scallop code path currently calling into bash
+    static ERROR_KIND: Cell<Option<ErrorKind>> = Cell::new(None);
+    // warnings gathered by an active warnings() call
+    static WARNINGS: RefCell<Option<Vec<Warning>>> = RefCell::new(None);
+    // messages gathered by an active capture() call
+    static CAPTURED: RefCell<Option<Vec<String>>> = RefCell::new(None);
 }
 
 /// Wrapper to convert internal bash errors into native errors.
 #[no_mangle]
 pub(crate) extern "C" fn bash_error(msg: *mut c_char) {
+    // read errno before anything else can change it
+    let errno = io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or_default();
     let msg = unsafe { CStr::from_ptr(msg).to_string_lossy() };
     // strip shell name prefix that bash adds
     let msg = msg.strip_prefix("scallop: ").unwrap_or(&msg);
     if !msg.is_empty() {
-        LAST_ERROR.with(|prev| {
-            let err = io::Error::last_os_error();
-            // convert bash IO errors into scallop IO errors
-            let e = match err.raw_os_error() {
-                Some(v) if v != 0 => Error::IO(err.kind(), msg.to_string()),
-                _ => Error::Base(msg.to_string()),
-            };
-            *prev.borrow_mut() = Some(e);
+        // convert bash IO errors into scallop IO errors
+        let e = match errno {
+            v if v != 0 && is_os_error(msg, v) => {
+                Error::IO(msg.to_string(), io::Error::from_raw_os_error(v))
+            }
+            _ => {
+                let kind = ERROR_KIND
+                    .with(|k| k.get())
+                    .unwrap_or(match builtin_level() {
+                        0 => ErrorKind::Other,
+                        _ => ErrorKind::Builtin,
+                    });
+                Error::Bash(BashError::new(kind, msg))
+            }
+        };
+
+        // errors raised in subshells are passed to the main process
+        if in_subshell() {
+            channel::send(&e);
+        } else {
+            raise(e);
+        }
+    }
+}
+
+/// Determine if a bash error message was caused by a given errno value.
+///
+/// System errors from bash end with the errno description while errno values left over from
+/// earlier calls are unrelated to the message.
+fn is_os_error(msg: &str, errno: i32) -> bool {
+    let desc = unsafe { CStr::from_ptr(libc::strerror(errno)).to_string_lossy() };
+    msg.ends_with(&format!(": {desc}"))
+}
+
+/// Register an error as the most recent one or pass it to an active capture() call.
+fn raise(e: Error) {
+    let captured = CAPTURED.with(|c| match c.borrow_mut().as_mut() {
+        Some(msgs) => {
+            msgs.push(e.to_string());
+            true
+        }
+        None => false,
+    });
+
+    if !captured {
+        ERRORS.with(|errors| {
+            if let Some(prev) = errors.borrow_mut().last_mut() {
+                *prev = Some(e);
+            }
         });
     }
 }
 
+/// Run a function gathering all bash error messages raised during it instead of only
+/// tracking the most recent error.
+pub(crate) fn capture<F: FnOnce() -> T, T>(func: F) -> (T, Vec<String>) {
+    let prev = CAPTURED.with(|c| c.borrow_mut().replace(vec![]));
+    let value = func();
+    let msgs = CAPTURED.with(|c| c.replace(prev)).unwrap_or_default();
+    (value, msgs)
+}
+
 /// Retrieve the most recent internal bash error.
 #[inline]
 pub fn last_error() -> Option<Error> {
-    Shell::raise_shm_error();
-    LAST_ERROR.with(|prev| prev.borrow_mut().take())
+    recv();
+    ERRORS.with(|errors| errors.borrow_mut().last_mut().and_then(|e| e.take()))
+}
+
+/// Raise errors written by bash to shared memory and errors sent by scallop builtins,
+/// possibly from subshells.
+fn recv() {
+    let errors = channel::recv();
+    if let Some(msg) = channel::recv_shm() {
+        let msg = msg.to_string_lossy();
+        let msg = msg.strip_prefix("scallop: ").unwrap_or(&msg);
+        // skip errors already sent by the error handler in a subshell
+        if !msg.is_empty() && !errors.iter().any(|e| e.to_string() == msg) {
+            // the location is unknown since the error was raised in another process
+            raise(Error::Bash(BashError {
+                file: None,
+                line: None,
+                kind: ErrorKind::Other,
+                command: None,
+                status: None,
+                message: msg.to_string(),
+            }));
+        }
+    }
+    for e in errors {
+        raise(e);
+    }
+}
+
+/// Error scope for a call into bash, restoring the enclosing scope on drop.
+///
+/// Errors raised during its lifetime are only visible within it so re-entrant calls, e.g. a
+/// builtin sourcing code that runs other builtins, can't take enclosing errors. Unchecked
+/// errors, including those sent via the channel during the scope, are passed to the
+/// enclosing scope on drop unless it already has a pending error.
+///
+/// Each scallop call into bash and each builtin run via [`crate::builtins::make_builtin`]
+/// uses its own scope, so nesting follows both the call depth and the builtin level. It's
+/// only public since that macro expands to it in other crates.
+#[doc(hidden)]
+pub struct ScopedErrors {
+    level: usize,
+}
+
+impl Default for ScopedErrors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScopedErrors {
+    /// Start a new error scope, first receiving pending errors into the enclosing scope.
+    pub fn new() -> Self {
+        // errors sent before the scope starts belong to the enclosing one
+        recv();
+        ERRORS.with(|errors| {
+            let mut errors = errors.borrow_mut();
+            errors.push(None);
+            Self {
+                level: errors.len() - 1,
+            }
+        })
+    }
+}
+
+impl Drop for ScopedErrors {
+    fn drop(&mut self) {
+        // errors sent during the scope belong to it
+        recv();
+        ERRORS.with(|errors| {
+            let mut errors = errors.borrow_mut();
+            // inner scopes skipped by a longjmp are dropped along with this one
+            let level = self.level.min(errors.len());
+            let unchecked = errors.drain(level..).flatten().last();
+            // pending errors in the enclosing scope are kept
+            if let (Some(e), Some(prev @ None)) = (unchecked, errors.last_mut()) {
+                *prev = Some(e);
+            }
+        });
+    }
 }
 
 /// Return the most recent error if one exists, otherwise Ok(ExecStatus::Success).
@@ -64,8 +453,39 @@ pub fn ok_or_error() -> Result<ExecStatus> {
 /// Wrapper to support outputting log messages for bash warnings.
 #[no_mangle]
 pub(crate) extern "C" fn bash_warning(msg: *mut c_char) {
-    if let Ok(msg) = unsafe { CStr::from_ptr(msg).to_str() } {
-        warn!(msg);
+    let msg = unsafe { CStr::from_ptr(msg).to_string_lossy() };
+    // strip shell name prefix that bash adds
+    let msg = msg.strip_prefix("scallop: ").unwrap_or(&msg);
+    warn!(msg);
+    WARNINGS.with(|w| {
+        if let Some(warnings) = w.borrow_mut().as_mut() {
+            warnings.push(Warning::new(msg));
+        }
+    });
+}
+
+/// Run a function gathering all bash warnings raised during it.
+///
+/// Warnings are still logged and nested calls also pass their warnings to the enclosing
+/// call.
+pub fn warnings<F: FnOnce() -> T, T>(func: F) -> (T, Vec<Warning>) {
+    let prev = WARNINGS.with(|w| w.borrow_mut().replace(vec![]));
+    let value = func();
+    let warnings = WARNINGS.with(|w| w.replace(prev)).unwrap_or_default();
+    WARNINGS.with(|w| {
+        if let Some(outer) = w.borrow_mut().as_mut() {
+            outer.extend(warnings.iter().cloned());
+        }
+    });
+    (value, warnings)
+}
+
+/// Run a fallible function, converting the first bash warning raised during it into an error.
+pub fn deny_warnings<F: FnOnce() -> Result<T>, T>(func: F) -> Result<T> {
+    let (value, warnings) = warnings(func);
+    match warnings.into_iter().next() {
+        Some(w) => Err(w.into()),
+        None => value,
     }
 }
 
@@ -75,3 +495,248 @@ pub(crate) extern "C" fn stderr_output(msg: *mut c_char) {
     let msg = unsafe { CStr::from_ptr(msg).to_string_lossy() };
     eprintln!("{msg}");
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::builtins::declare;
+    use crate::source::{self, hooks, Source};
+
+    use super::*;
+
+    #[test]
+    fn test_bash_error() {
+        // runtime errors are located via the innermost source file and executing line
+        let err = source::string_at("VAR=1\n\nlocal VAR", "file.sh", 1).unwrap_err();
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.file.as_deref(), Some("file.sh"));
+                assert_eq!(e.line, Some(3));
+                assert_eq!(e.command.as_deref(), Some("local"));
+                assert_eq!(e.status, Some(ExecStatus::Failure(1)));
+                assert_eq!(e.description(), "can only be used in a function");
+                assert_eq!(e.to_string(), e.message);
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+
+        // functions use the file they were defined in
+        source::string_at("f() {\n  local -Z\n}", "lib.sh", 1).unwrap();
+        let err = source::string_at("VAR=1\nf", "file.sh", 1).unwrap_err();
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.file.as_deref(), Some("lib.sh"));
+                assert_eq!(e.line, Some(2));
+                assert_eq!(e.command.as_deref(), Some("local"));
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+
+        // exit statuses come from the failing call
+        let err = source::string("declare -Z").unwrap_err();
+        assert_eq!(err.status(), Some(ExecStatus::Failure(2)));
+
+        // unnamed strings have no file
+        let err = source::string("local VAR").unwrap_err();
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.file, None);
+                assert!(e.line.is_some());
+                assert_eq!(e.description(), "can only be used in a function");
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+
+        // parser errors are located via the input being parsed
+        let err = Source::new()
+            .name("file.sh")
+            .errexit(true)
+            .string("VAR=1\nfi")
+            .unwrap_err();
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.file.as_deref(), Some("file.sh"));
+                assert_eq!(e.line, Some(2));
+                assert_eq!(e.status, Some(ExecStatus::Failure(2)));
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_source_errors() {
+        let err = source::string_at("VAR=1\nlocal VAR", "file.sh", 1).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("local: can only be used in a function"));
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.command.as_deref(), Some("local"));
+                assert_eq!(e.description(), "can only be used in a function");
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+
+        let err = source::string_errexit("true\nfalse").unwrap_err();
+        assert_eq!(err.status(), Some(ExecStatus::Failure(1)));
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.file.as_deref(), Some("scallop::source::string"));
+                assert_eq!(e.line, Some(2));
+                assert_eq!(e.command.as_deref(), Some("false"));
+                assert_eq!(e.status, Some(ExecStatus::Failure(1)));
+                assert_eq!(e.description(), "command failed: false");
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let err = source::string_errexit("VAR=1\nfi").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Syntax);
+
+        let err = declare(&["-Z"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Builtin);
+        assert_eq!(err.kind().to_string(), "builtin error");
+
+        // builtins run by sourced code are classified by their nesting level
+        let err = source::string_errexit("declare -Z").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Builtin);
+
+        // unclassified code paths
+        let err = source::string_errexit("false").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(Error::Base("msg".into()).kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let (value, found) = warnings(|| source::string_at(": <<EOF\nfoo", "file.sh", 1));
+        value.unwrap();
+        assert_eq!(found.len(), 1);
+        let w = &found[0];
+        assert_eq!(w.file.as_deref(), Some("file.sh"));
+        assert_eq!(w.line, Some(2));
+        assert!(w.message.starts_with("file.sh: line 2: warning: "), "{}", w.message);
+        assert!(w
+            .description()
+            .starts_with("here-document at line 1 delimited by end-of-file"));
+        assert_eq!(w.to_string(), w.message);
+
+        // warnings from nested calls are passed to the enclosing call
+        let (_, outer) = warnings(|| {
+            let (_, inner) = warnings(|| source::string(": <<EOF\nfoo"));
+            assert_eq!(inner.len(), 1);
+        });
+        assert_eq!(outer.len(), 1);
+
+        // no warnings
+        let (_, found) = warnings(|| source::string(": <<EOF\nfoo\nEOF"));
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_deny_warnings() {
+        deny_warnings(|| source::string("VAR=1")).unwrap();
+        let err = deny_warnings(|| source::string(": <<EOF\nfoo")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Warning);
+        assert!(err.to_string().contains("delimited by end-of-file"));
+    }
+
+    #[test]
+    fn test_io_errors() {
+        use std::error::Error as _;
+
+        let os_error = |e: &Error| {
+            e.source()
+                .and_then(|e| e.downcast_ref::<io::Error>())
+                .and_then(|e| e.raw_os_error())
+        };
+
+        let err = source::string("source /nonexistent/path").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(os_error(&err), Some(libc::ENOENT));
+        assert_eq!(os_error(&err.clone()), Some(libc::ENOENT));
+
+        let dir = tempfile::tempdir().unwrap();
+        let err = source::string(format!("source {}", dir.path().display())).unwrap_err();
+        assert_eq!(os_error(&err), Some(libc::EISDIR));
+
+        // errors passed from builtins and subshells keep their errno value
+        let id = hooks::register(|_| {
+            Err(Error::IO("denied".into(), io::Error::from_raw_os_error(libc::EACCES)))
+        });
+        let err = source::string("source /dev/null").unwrap_err();
+        hooks::unregister(id);
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(os_error(&err), Some(libc::EACCES));
+        let err = source::string("(source /nonexistent/path)").unwrap_err();
+        assert_eq!(os_error(&err), Some(libc::ENOENT));
+
+        // stale errno values are ignored
+        assert!(std::fs::metadata("/nonexistent/path").is_err());
+        let err = source::string("local VAR").unwrap_err();
+        assert_ne!(err.kind(), ErrorKind::Io);
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_scoped_errors() {
+        assert!(last_error().is_none());
+        raise(Error::Base("outer".into()));
+
+        // calls only see errors raised within them
+        source::string("VAR=1").unwrap();
+        let err = source::string("local VAR").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("local: can only be used in a function"));
+
+        {
+            let _errors = ScopedErrors::new();
+            assert!(last_error().is_none());
+            raise(Error::Base("inner".into()));
+            {
+                let _errors = ScopedErrors::new();
+                assert!(last_error().is_none());
+            }
+            assert_eq!(last_error().unwrap().to_string(), "inner");
+        }
+        assert_eq!(last_error().unwrap().to_string(), "outer");
+        assert!(last_error().is_none());
+
+        // unchecked errors are passed to the enclosing scope
+        {
+            let _errors = ScopedErrors::new();
+            raise(Error::Base("unchecked".into()));
+        }
+        assert_eq!(last_error().unwrap().to_string(), "unchecked");
+        assert!(last_error().is_none());
+
+        // pending errors in the enclosing scope aren't overwritten
+        raise(Error::Base("pending".into()));
+        {
+            let _errors = ScopedErrors::new();
+            raise(Error::Base("unchecked".into()));
+        }
+        assert_eq!(last_error().unwrap().to_string(), "pending");
+        assert!(last_error().is_none());
+
+        // errors sent during a scope are received when it ends
+        {
+            let _errors = ScopedErrors::new();
+            channel::send(&Error::Base("sent".into()));
+        }
+        raise(Error::Base("later".into()));
+        assert_eq!(last_error().unwrap().to_string(), "later");
+        assert!(last_error().is_none());
+
+        // errors sent before a scope starts belong to the enclosing one
+        channel::send(&Error::Base("sent".into()));
+        {
+            let _errors = ScopedErrors::new();
+            assert!(last_error().is_none());
+        }
+        assert_eq!(last_error().unwrap().to_string(), "sent");
+    }
+}
@@ -0,0 +1,428 @@
+use std::borrow::Cow;
+use std::ffi::{c_void, CStr, CString};
+use std::fs::File;
+use std::io::Write;
+use std::os::raw::{c_char, c_int};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, io, mem, ptr};
+
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::libc;
+use nix::unistd::{getpid, mkstemp, unlink};
+use once_cell::sync::OnceCell;
+
+use crate::builtins::ExecStatus;
+use crate::shell::Shell;
+
+use super::{BashError, Error, ErrorKind};
+
+// Errors are appended as length-prefixed records to an unlinked temporary file shared by
+// all forked subshells. Unlike a pipe, writers never block on an unread buffer while the
+// main process waits on them.
+static CHANNEL: OnceCell<File> = OnceCell::new();
+
+// Count of records sent, mapped as shared memory so the count includes records sent by
+// forked subshells.
+static SENT: OnceCell<&'static AtomicUsize> = OnceCell::new();
+
+// Value of the sent count when the current process last received errors.
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+/// Size of the shared memory buffer bash's C code writes errors to via `shm_error()`.
+pub(crate) const SHM_SIZE: c_int = 4096;
+
+static SHM: OnceCell<Shm> = OnceCell::new();
+
+/// Shared memory buffer allocated by bash, shared with forked subshells.
+struct Shm(*mut c_char);
+
+// The buffer is only accessed from the thread running bash.
+unsafe impl Send for Shm {}
+unsafe impl Sync for Shm {}
+
+/// Create the error channel, must be called before any subshells are forked.
+///
+/// The given shared memory buffer must be [`SHM_SIZE`] bytes.
+pub(crate) fn init(shm: *mut c_void) -> nix::Result<()> {
+    let template = env::temp_dir().join("scallop-errors.XXXXXX");
+    let (fd, path) = mkstemp(&template)?;
+    let file = unsafe { File::from_raw_fd(fd) };
+    unlink(&path)?;
+    // external commands don't inherit the channel
+    fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+    fcntl(fd, FcntlArg::F_SETFL(OFlag::O_APPEND))?;
+
+    let sent = unsafe {
+        let size = mem::size_of::<AtomicUsize>();
+        let prot = libc::PROT_READ | libc::PROT_WRITE;
+        let flags = libc::MAP_SHARED | libc::MAP_ANONYMOUS;
+        match libc::mmap(ptr::null_mut(), size, prot, flags, -1, 0) {
+            libc::MAP_FAILED => return Err(Errno::last()),
+            // anonymous mappings are zero-initialized
+            addr => &*(addr as *const AtomicUsize),
+        }
+    };
+
+    CHANNEL.set(file).ok();
+    SENT.set(sent).ok();
+    SHM.set(Shm(shm as *mut c_char)).ok();
+    Ok(())
+}
+
+/// Take the error written to the shared memory buffer if one exists.
+///
+/// Note that this is ignored if the shell wasn't initialized, e.g. using scallop as a shared
+/// library for dynamic bash builtins.
+pub(crate) fn recv_shm() -> Option<CString> {
+    let shm = SHM.get()?;
+    unsafe {
+        if *shm.0 == 0 {
+            return None;
+        }
+        let msg = CStr::from_ptr(shm.0).to_owned();
+        ptr::write_bytes(shm.0, 0, SHM_SIZE as usize);
+        Some(msg)
+    }
+}
+
+/// Exclusive lock on the channel shared across processes, released on drop.
+struct Lock<'a>(&'a File);
+
+impl<'a> Lock<'a> {
+    fn new(file: &'a File) -> Self {
+        lock(file, libc::F_WRLCK);
+        Self(file)
+    }
+}
+
+impl Drop for Lock<'_> {
+    fn drop(&mut self) {
+        lock(self.0, libc::F_UNLCK);
+    }
+}
+
+fn lock(file: &File, kind: i32) {
+    let mut flock: libc::flock = unsafe { mem::zeroed() };
+    flock.l_type = kind as _;
+    flock.l_whence = libc::SEEK_SET as _;
+    loop {
+        match fcntl(file.as_raw_fd(), FcntlArg::F_SETLKW(&flock)) {
+            Err(Errno::EINTR) => continue,
+            result => {
+                result.expect("failed locking error channel");
+                break;
+            }
+        }
+    }
+}
+
+/// Send an error to be raised by the next error check in the current or main process.
+///
+/// System errors are received as [`Error::IO`] with the same errno value while all other
+/// errors are received as bash errors.
+pub(crate) fn send(err: &Error) {
+    let file = CHANNEL.get().expect("uninitialized shell");
+    let (err, errno) = match err {
+        Error::Bash(e) => (Cow::Borrowed(e), None),
+        e => {
+            let errno = match e {
+                Error::IO(_, e) => e.raw_os_error(),
+                _ => None,
+            };
+            let err = BashError {
+                file: None,
+                line: None,
+                kind: e.kind(),
+                command: None,
+                status: e.status(),
+                message: e.to_string(),
+            };
+            (Cow::Owned(err), errno)
+        }
+    };
+    let mut data = vec![];
+    encode(&mut data, &err, errno);
+
+    let mut record = Vec::with_capacity(data.len() + 8);
+    record.extend((data.len() as u32).to_ne_bytes());
+    record.extend(getpid().as_raw().to_ne_bytes());
+    record.extend(data);
+
+    let _lock = Lock::new(file);
+    let mut file = file;
+    file.write_all(&record)
+        .expect("failed writing to error channel");
+    if let Some(sent) = SENT.get() {
+        sent.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// Receive all pending errors for the current process in the order they were sent.
+///
+/// The main process receives errors sent from all subshells while subshells only receive
+/// their own.
+pub(crate) fn recv() -> Vec<Error> {
+    let (file, sent) = match (CHANNEL.get(), SENT.get()) {
+        (Some(file), Some(sent)) => (file, sent),
+        _ => return vec![],
+    };
+
+    // skip locking when nothing was sent since the last check
+    if sent.load(Ordering::Acquire) == RECEIVED.load(Ordering::Relaxed) {
+        return vec![];
+    }
+
+    let _lock = Lock::new(file);
+    RECEIVED.store(sent.load(Ordering::Acquire), Ordering::Relaxed);
+    let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+    let mut data = vec![0; len as usize];
+    file.read_exact_at(&mut data, 0)
+        .expect("failed reading from error channel");
+
+    let pid = getpid().as_raw();
+    let main = pid == Shell::pid().as_raw();
+    let mut errors = vec![];
+    let mut remaining = vec![];
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let len = u32::from_ne_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let sender = i32::from_ne_bytes(data[pos + 4..pos + 8].try_into().unwrap());
+        let record = match data.get(pos..pos + 8 + len) {
+            Some(record) => record,
+            None => break,
+        };
+        if main || sender == pid {
+            errors.push(decode(&record[8..]));
+        } else {
+            remaining.extend(record);
+        }
+        pos += 8 + len;
+    }
+
+    // leave records for other processes in place
+    file.set_len(0).expect("failed truncating error channel");
+    let mut file = file;
+    file.write_all(&remaining)
+        .expect("failed writing to error channel");
+
+    errors
+}
+
+fn encode(data: &mut Vec<u8>, err: &BashError, errno: Option<i32>) {
+    data.push(match err.kind {
+        ErrorKind::Syntax => 0,
+        ErrorKind::Readonly => 1,
+        ErrorKind::Builtin => 2,
+        ErrorKind::Io => 3,
+        ErrorKind::Warning => 4,
+        ErrorKind::Other => 5,
+    });
+
+    match err.status {
+        None => data.push(0),
+        Some(ExecStatus::Success) => data.push(1),
+        Some(ExecStatus::Failure(n)) => {
+            data.push(2);
+            data.extend(n.to_ne_bytes());
+        }
+        Some(ExecStatus::Error) => data.push(3),
+    }
+
+    match errno {
+        None => data.push(0),
+        Some(n) => {
+            data.push(1);
+            data.extend(n.to_ne_bytes());
+        }
+    }
+
+    match err.line {
+        None => data.push(0),
+        Some(n) => {
+            data.push(1);
+            data.extend((n as u64).to_ne_bytes());
+        }
+    }
+
+    for s in [err.file.as_deref(), err.command.as_deref(), Some(err.message.as_str())] {
+        match s {
+            None => data.push(0),
+            Some(s) => {
+                data.push(1);
+                data.extend((s.len() as u32).to_ne_bytes());
+                data.extend(s.as_bytes());
+            }
+        }
+    }
+}
+
+/// Cursor over an encoded error.
+struct Decoder<'a>(&'a [u8]);
+
+impl Decoder<'_> {
+    fn bytes(&mut self, n: usize) -> &[u8] {
+        let (value, rest) = self.0.split_at(n);
+        self.0 = rest;
+        value
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn i32(&mut self) -> i32 {
+        i32::from_ne_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_ne_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_ne_bytes(self.bytes(8).try_into().unwrap())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        match self.u8() {
+            0 => None,
+            _ => {
+                let len = self.u32() as usize;
+                Some(String::from_utf8_lossy(self.bytes(len)).into_owned())
+            }
+        }
+    }
+}
+
+fn decode(data: &[u8]) -> Error {
+    let mut d = Decoder(data);
+    let kind = match d.u8() {
+        0 => ErrorKind::Syntax,
+        1 => ErrorKind::Readonly,
+        2 => ErrorKind::Builtin,
+        3 => ErrorKind::Io,
+        4 => ErrorKind::Warning,
+        _ => ErrorKind::Other,
+    };
+    let status = match d.u8() {
+        0 => None,
+        1 => Some(ExecStatus::Success),
+        2 => Some(ExecStatus::Failure(d.i32())),
+        _ => Some(ExecStatus::Error),
+    };
+    let errno = match d.u8() {
+        0 => None,
+        _ => Some(d.i32()),
+    };
+    let line = match d.u8() {
+        0 => None,
+        _ => Some(d.u64() as usize),
+    };
+    let file = d.string();
+    let command = d.string();
+    let message = d.string().unwrap_or_default();
+
+    match errno {
+        Some(n) => Error::IO(message, io::Error::from_raw_os_error(n)),
+        None => Error::Bash(BashError {
+            file,
+            line,
+            kind,
+            command,
+            status,
+            message,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::bash;
+    use crate::error::{capture, last_error};
+    use crate::source::{self, hooks};
+
+    use super::*;
+
+    #[test]
+    fn test_send_recv() {
+        let err = BashError {
+            file: Some("file.sh".into()),
+            line: Some(3),
+            kind: ErrorKind::Builtin,
+            command: Some("cmd".into()),
+            status: Some(ExecStatus::Failure(2)),
+            message: "file.sh: line 3: cmd: error: ".to_string() + &"x".repeat(10000),
+        };
+        assert!(recv().is_empty());
+        send(&Error::Bash(err.clone()));
+        send(&Error::Base("msg".into()));
+        send(&Error::IO("io".into(), io::Error::from_raw_os_error(libc::ENOENT)));
+        let errors = recv();
+        assert_eq!(errors.len(), 3);
+        match &errors[0] {
+            Error::Bash(e) => assert_eq!(e, &err),
+            e => panic!("invalid error: {e:?}"),
+        }
+        assert_eq!(errors[1].to_string(), "msg");
+
+        // system errors keep their errno value
+        match &errors[2] {
+            Error::IO(s, e) => {
+                assert_eq!(s, "io");
+                assert_eq!(e.raw_os_error(), Some(libc::ENOENT));
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+        assert!(recv().is_empty());
+    }
+
+    #[test]
+    fn test_shm() {
+        assert!(recv_shm().is_none());
+        let msg = CString::new("shm error").unwrap();
+        unsafe { bash::shm_error(msg.as_ptr() as *mut _) };
+        assert!(last_error().unwrap().to_string().ends_with("shm error"));
+        assert!(recv_shm().is_none());
+    }
+
+    #[test]
+    fn test_subshells() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.sh");
+        fs::write(&path, "VAR=1\n").unwrap();
+
+        let id = hooks::register(|e| Err(Error::Base(format!("denied: {}", e.path.display()))));
+        let path = path.display();
+        let (_, msgs) = capture(|| {
+            source::string(format!("(source {path}); ( (source {path}) ); echo $(source {path})"))
+        });
+        hooks::unregister(id);
+
+        assert_eq!(msgs.len(), 3);
+        for msg in msgs {
+            assert_eq!(msg, format!("source: error: denied: {path}"));
+        }
+
+        // errors raised by bash keep their components
+        let err = source::string_at("VAR=1\n(local VAR)", "file.sh", 1).unwrap_err();
+        match err {
+            Error::Bash(e) => {
+                assert_eq!(e.kind, ErrorKind::Builtin);
+                assert_eq!(e.file.as_deref(), Some("file.sh"));
+                assert_eq!(e.line, Some(2));
+                assert_eq!(e.command.as_deref(), Some("local"));
+                assert_eq!(e.status, Some(ExecStatus::Failure(1)));
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+
+        // as do multiple errors from the same subshell
+        let (_, msgs) = capture(|| source::string("(local A; local B)"));
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs[0].ends_with("local: can only be used in a function"));
+    }
+}
@@ -2,9 +2,10 @@ use std::ffi::CString;
 use std::os::raw::c_char;
 use std::ptr;
 
-use crate::bash;
 use crate::builtins::ExecStatus;
-use crate::error::ok_or_error;
+use crate::error::{ok_or_error, ScopedErrors};
+use crate::positional::ScopedPositional;
+use crate::{bash, Error};
 
 #[derive(Debug)]
 pub struct Function<'a> {
@@ -15,6 +16,7 @@ pub struct Function<'a> {
 impl Function<'_> {
     /// Execute a given shell function.
     pub fn execute(&mut self, args: &[&str]) -> crate::Result<ExecStatus> {
+        let _errors = ScopedErrors::new();
         let args = [&[self.name.as_str()], args].concat();
         let arg_strs: Vec<CString> = args.iter().map(|s| CString::new(*s).unwrap()).collect();
         let mut arg_ptrs: Vec<*mut c_char> =
@@ -40,17 +42,23 @@ pub fn find<'a, S: AsRef<str>>(name: S) -> Option<Function<'a>> {
     })
 }
 
-/// Run a function in bash function scope.
-pub fn bash_func<S: AsRef<str>, F: FnOnce()>(name: S, func: F) {
-    let func_name = CString::new(name.as_ref()).unwrap();
+/// Run a function in bash function scope using the given positional parameters.
+///
+/// Fails if the name or any parameter contains a nul byte.
+pub fn bash_func<S: AsRef<str>, F: FnOnce()>(name: S, args: &[&str], func: F) -> crate::Result<()> {
+    let name = name.as_ref();
+    let func_name = CString::new(name)
+        .map_err(|_| Error::Base(format!("function name contains nul byte: {name:?}")))?;
     unsafe { bash::push_context(func_name.as_ptr() as *mut _, 0, bash::TEMPORARY_ENV) };
-    func();
+    let result = ScopedPositional::new(args).map(|_args| func());
     unsafe { bash::pop_context() };
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use crate::builtins::local;
+    use crate::positional;
     use crate::source;
     use crate::variables::{bind, string_value};
 
@@ -77,10 +85,28 @@ mod tests {
     #[test]
     fn test_bash_func() {
         bind("VAR", "outer", None, None).unwrap();
-        bash_func("func_name", || {
+        bash_func("func_name", &[], || {
             local(&["VAR=inner"]).unwrap();
             assert_eq!(string_value("VAR").unwrap(), "inner");
-        });
+        })
+        .unwrap();
         assert_eq!(string_value("VAR").unwrap(), "outer");
     }
+
+    #[test]
+    fn test_bash_func_args() {
+        positional::set(&["outer"]).unwrap();
+        bash_func("func_name", &["a", "b"], || {
+            assert_eq!(positional::get(), ["a", "b"]);
+            source::string("VAR=\"$#:$@\"").unwrap();
+            assert_eq!(string_value("VAR").unwrap(), "2:a b");
+        })
+        .unwrap();
+        assert_eq!(positional::get(), ["outer"]);
+
+        // invalid values
+        assert!(bash_func("func_name", &["a\0b"], || panic!("called")).is_err());
+        assert!(bash_func("func\0name", &[], || panic!("called")).is_err());
+        assert_eq!(positional::get(), ["outer"]);
+    }
 }
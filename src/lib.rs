@@ -1,16 +1,22 @@
 #![warn(unreachable_pub)]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+pub mod analysis;
 pub mod bash;
+pub mod build;
 pub mod builtins;
 pub mod command;
 pub mod error;
 pub mod functions;
+pub mod positional;
+pub mod posix;
+pub mod quote;
 pub mod shell;
 pub mod source;
+pub mod syntax;
 pub(crate) mod test;
 pub mod traits;
 pub mod variables;
 
-pub use self::error::{Error, Result};
+pub use self::error::{Error, ErrorKind, Result, Warning};
 pub use shell::Shell;
@@ -0,0 +1,148 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+use crate::builtins::ExecStatus;
+use crate::traits::*;
+use crate::{bash, Error};
+
+/// Get the current positional parameters, i.e. the values of `$@`.
+///
+/// Values that aren't valid UTF-8 are converted lossily.
+pub fn get() -> Vec<String> {
+    let words = unsafe { bash::list_rest_of_args().into_words(true) };
+    words
+        .iter_os()
+        .map(|s| s.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Return the number of positional parameters, i.e. the value of `$#`.
+pub fn len() -> usize {
+    unsafe { bash::number_of_args() as usize }
+}
+
+/// Replace the current positional parameters with the given values.
+///
+/// Fails if any value contains a nul byte.
+pub fn set(args: &[&str]) -> crate::Result<()> {
+    set_os(&args.iter().map(OsStr::new).collect::<Vec<_>>())
+}
+
+/// Replace the current positional parameters with the given raw values.
+pub(crate) fn set_os(args: &[&OsStr]) -> crate::Result<()> {
+    if let Some(s) = args.iter().find(|s| s.as_bytes().contains(&0)) {
+        return Err(Error::Base(format!("positional parameter contains nul byte: {s:?}")));
+    }
+    let args = Words::from_iter(args.iter().copied());
+    unsafe { bash::remember_args((&args).into(), 1) };
+    Ok(())
+}
+
+/// Shift the positional parameters to the left by a given amount.
+pub fn shift(n: usize) -> crate::Result<ExecStatus> {
+    if n > len() {
+        return Err(Error::Base(format!("{n}: shift count out of range")));
+    }
+    unsafe { bash::shift_args(n as i32) };
+    Ok(ExecStatus::Success)
+}
+
+/// Positional parameters that revert to their original values when leaving scope.
+#[derive(Debug)]
+pub struct ScopedPositional {
+    _private: (),
+}
+
+impl ScopedPositional {
+    /// Save the current positional parameters and replace them with the given values.
+    ///
+    /// Fails if any value contains a nul byte.
+    pub fn new(args: &[&str]) -> crate::Result<Self> {
+        Self::new_os(&args.iter().map(OsStr::new).collect::<Vec<_>>())
+    }
+
+    /// Save the current positional parameters and replace them with the given raw values.
+    pub(crate) fn new_os(args: &[&OsStr]) -> crate::Result<Self> {
+        unsafe { bash::push_dollar_vars() };
+        let scope = ScopedPositional { _private: () };
+        set_os(args)?;
+        Ok(scope)
+    }
+}
+
+impl Drop for ScopedPositional {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { bash::pop_dollar_vars() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source;
+    use crate::variables::string_value;
+
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        assert!(get().is_empty());
+        assert_eq!(len(), 0);
+        set(&["a", "b", "c"]).unwrap();
+        assert_eq!(get(), ["a", "b", "c"]);
+        assert_eq!(len(), 3);
+        source::string("VAR=\"$#:$2\"").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "3:b");
+        set(&[]).unwrap();
+        assert!(get().is_empty());
+
+        // non-UTF-8 values
+        source::string("set -- $'a\\xffb' c").unwrap();
+        assert_eq!(get(), ["a\u{fffd}b", "c"]);
+
+        // values containing nul bytes
+        assert!(set(&["a\0b"]).is_err());
+        assert_eq!(get(), ["a\u{fffd}b", "c"]);
+        assert!(ScopedPositional::new(&["a\0b"]).is_err());
+        assert_eq!(get(), ["a\u{fffd}b", "c"]);
+    }
+
+    #[test]
+    fn test_many_args() {
+        // more args than bash stores in its fixed $1..$9 array
+        let args: Vec<_> = (1..=12).map(|i| i.to_string()).collect();
+        let args: Vec<_> = args.iter().map(|s| s.as_str()).collect();
+        set(&args).unwrap();
+        assert_eq!(get(), args);
+        source::string("VAR=${11}").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "11");
+    }
+
+    #[test]
+    fn test_shift() {
+        set(&["a", "b", "c"]).unwrap();
+        shift(0).unwrap();
+        assert_eq!(get(), ["a", "b", "c"]);
+        shift(1).unwrap();
+        assert_eq!(get(), ["b", "c"]);
+        shift(2).unwrap();
+        assert!(get().is_empty());
+        let err = shift(1).unwrap_err();
+        assert_eq!(err.to_string(), "1: shift count out of range");
+    }
+
+    #[test]
+    fn test_scoped_positional() {
+        set(&["outer"]).unwrap();
+        {
+            let _args = ScopedPositional::new(&["inner1", "inner2"]).unwrap();
+            assert_eq!(get(), ["inner1", "inner2"]);
+            {
+                let _args = ScopedPositional::new(&[]).unwrap();
+                assert!(get().is_empty());
+            }
+            assert_eq!(get(), ["inner1", "inner2"]);
+        }
+        assert_eq!(get(), ["outer"]);
+    }
+}
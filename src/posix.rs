@@ -0,0 +1,469 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::{fmt, fs};
+
+use crate::command::ast::{Node, NodeKind, Redirect, Simple, Word};
+use crate::command::parse_script;
+use crate::command::visit::{visit_script, walk_compound, walk_redirect, walk_simple, Visitor};
+use crate::Error;
+
+/// Bash-specific construct unsupported by POSIX `sh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Bashism {
+    /// `arr=(a b)`, `${arr[0]}`, or `declare -a arr`
+    Array,
+    /// `[[ expr ]]`
+    DoubleBracket,
+    /// `local var`
+    Local,
+    /// `$'...'`
+    AnsiCQuote,
+    /// `<(cmd)` or `>(cmd)`
+    ProcessSubstitution,
+    /// `function name { ... }`
+    FunctionKeyword,
+    /// `[ a == b ]`
+    TestEquality,
+    /// `{a,b}` or `{1..3}`
+    BraceExpansion,
+}
+
+impl Bashism {
+    /// Return a short explanation of the incompatibility.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::Array => "arrays are not supported in POSIX sh",
+            Self::DoubleBracket => "[[ ]] is not supported in POSIX sh, use [ ] or test",
+            Self::Local => "local is not defined by POSIX sh",
+            Self::AnsiCQuote => "$'...' quoting is not supported in POSIX sh, use printf",
+            Self::ProcessSubstitution => {
+                "process substitution is not supported in POSIX sh, use a pipe or temp file"
+            }
+            Self::FunctionKeyword => {
+                "the function keyword is not supported in POSIX sh, use name() { ...; }"
+            }
+            Self::TestEquality => "== is not supported by POSIX test, use =",
+            Self::BraceExpansion => "brace expansion is not supported in POSIX sh",
+        }
+    }
+}
+
+impl fmt::Display for Bashism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.explanation())
+    }
+}
+
+/// Bashism found at a given line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Finding {
+    pub line: usize,
+    pub bashism: Bashism,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.bashism)
+    }
+}
+
+/// Check a string of bash code for constructs unsupported by POSIX `sh`.
+///
+/// Findings are sorted by line with duplicates on the same line merged.
+pub fn check<S: AsRef<str>>(s: S) -> crate::Result<Vec<Finding>> {
+    let s = s.as_ref();
+    let cmds = parse_script(s)?;
+    let mut checker = Checker {
+        lines: s.lines().collect(),
+        findings: Default::default(),
+        here_docs: Default::default(),
+    };
+    visit_script(&mut checker, &cmds);
+    // $'...' strings are translated by the parser so they're only found in the source
+    checker.ansi_c_quotes(s);
+    Ok(checker.findings.into_iter().collect())
+}
+
+/// Check a file of bash code for constructs unsupported by POSIX `sh`.
+pub fn check_file<P: AsRef<Path>>(path: P) -> crate::Result<Vec<Finding>> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path)
+        .map_err(|e| Error::IO(format!("failed reading file: {path:?}: {e}"), e))?;
+    check(data)
+}
+
+struct Checker<'a> {
+    lines: Vec<&'a str>,
+    findings: BTreeSet<Finding>,
+    /// Unquoted here-document delimiters.
+    here_docs: Vec<String>,
+}
+
+impl Checker<'_> {
+    fn add(&mut self, line: i32, bashism: Bashism) {
+        let line = line.max(1) as usize;
+        self.findings.insert(Finding { line, bashism });
+    }
+
+    /// Flag array assignments, e.g. `arr=(a b)` or `arr[1]=a`.
+    fn assignment(&mut self, s: &str, line: i32) {
+        if let Some((name, value)) = s.split_once('=') {
+            if name.contains('[') || value.starts_with('(') {
+                self.add(line, Bashism::Array);
+            }
+        }
+    }
+
+    /// Flag the `$'...'` strings in the source, skipping comments, here-document bodies, and
+    /// other quoted text.
+    fn ansi_c_quotes(&mut self, s: &str) {
+        let bytes = s.as_bytes();
+        let (mut single, mut double) = (false, false);
+        let mut pending = vec![];
+        let mut line = 1;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\n' => {
+                    line += 1;
+                    // skip the bodies of here-documents started on the previous line
+                    for (eof, strip) in pending.drain(..) {
+                        while i < bytes.len() {
+                            let start = i + 1;
+                            let end = s[start..].find('\n').map(|n| start + n).unwrap_or(s.len());
+                            let body = &s[start..end];
+                            let body = if strip {
+                                body.trim_start_matches('\t')
+                            } else {
+                                body
+                            };
+                            i = end;
+                            line += 1;
+                            if body == eof {
+                                break;
+                            }
+                        }
+                    }
+                }
+                b'\\' if !single => {
+                    if bytes.get(i + 1) == Some(&b'\n') {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                b'\'' if !double => single = !single,
+                b'"' if !single => double = !double,
+                b'#' if !single && !double && (i == 0 || bytes[i - 1].is_ascii_whitespace()) => {
+                    while i + 1 < bytes.len() && bytes[i + 1] != b'\n' {
+                        i += 1;
+                    }
+                }
+                b'<' if !single && !double && bytes.get(i + 1) == Some(&b'<') => {
+                    i += 1;
+                    if bytes.get(i + 1) == Some(&b'<') {
+                        // skip here-strings
+                        i += 1;
+                    } else {
+                        let strip = bytes.get(i + 1) == Some(&b'-');
+                        let rest = s[i + 1..].trim_start_matches('-');
+                        let start = s.len() - rest.trim_start_matches([' ', '\t']).len();
+                        let end = word_end(s, start);
+                        let eof = unquote(&s[start..end]);
+                        // match against the parsed here-documents to skip shift operators
+                        if let Some(pos) = self.here_docs.iter().position(|s| s == &eof) {
+                            self.here_docs.remove(pos);
+                            pending.push((eof, strip));
+                            i = end - 1;
+                        }
+                    }
+                }
+                b'$' if !single && !double && bytes.get(i + 1) == Some(&b'\'') => {
+                    self.add(line, Bashism::AnsiCQuote);
+                    // skip the string, allowing escaped quotes
+                    i += 2;
+                    while i < bytes.len() && bytes[i] != b'\'' {
+                        match bytes[i] {
+                            b'\\' => i += 1,
+                            b'\n' => line += 1,
+                            _ => (),
+                        }
+                        i += 1;
+                    }
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+}
+
+impl Visitor for Checker<'_> {
+    fn visit_simple(&mut self, cmd: &Simple, line: i32) {
+        if let Some(name) = cmd.name() {
+            let args = cmd.args();
+            match name.as_str() {
+                "local" => self.add(line, Bashism::Local),
+                "test" | "[" if args.iter().any(|w| w == "==") => {
+                    self.add(line, Bashism::TestEquality)
+                }
+                _ => (),
+            }
+
+            if let "local" | "declare" | "typeset" | "export" | "readonly" = name.as_str() {
+                for arg in args.iter().map(|w| w.as_str()) {
+                    if arg.starts_with('-') && arg.contains(['a', 'A']) {
+                        self.add(line, Bashism::Array);
+                    } else {
+                        self.assignment(arg, line);
+                    }
+                }
+            }
+        }
+        walk_simple(self, cmd, line);
+    }
+
+    fn visit_word(&mut self, word: &Word, line: i32) {
+        let s = word.as_str();
+        let quoted = quoted(s);
+        let bytes = s.as_bytes();
+
+        for (i, c) in bytes.iter().enumerate() {
+            match c {
+                b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                    let inner = &s[i + 2..];
+                    let inner = inner.strip_prefix(['#', '!']).unwrap_or(inner);
+                    let name = inner
+                        .bytes()
+                        .take_while(|c| c.is_ascii_alphanumeric() || *c == b'_')
+                        .count();
+                    if name > 0 && inner[name..].starts_with('[') {
+                        self.add(line, Bashism::Array);
+                    }
+                }
+                b'<' | b'>' if !quoted[i] && bytes.get(i + 1) == Some(&b'(') => {
+                    self.add(line, Bashism::ProcessSubstitution);
+                }
+                b'{' if !quoted[i]
+                    && (i == 0 || bytes[i - 1] != b'$')
+                    && brace_expansion(&s[i..], &quoted[i..]) =>
+                {
+                    self.add(line, Bashism::BraceExpansion);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn visit_assignment(&mut self, word: &Word, line: i32) {
+        self.assignment(word.as_str(), line);
+        self.visit_word(word, line);
+    }
+
+    fn visit_function(&mut self, name: &Word, body: &Node, line: i32) {
+        // bash doesn't track the keyword so check the source line of the definition
+        if let Some(src) = self.lines.get((line - 1) as usize) {
+            let mut tokens = src.split_whitespace();
+            while let Some(token) = tokens.next() {
+                if token == "function" && tokens.next() == Some(name.as_str()) {
+                    self.add(line, Bashism::FunctionKeyword);
+                    break;
+                }
+            }
+        }
+        self.visit_node(body);
+    }
+
+    fn visit_compound(&mut self, node: &Node) {
+        if let NodeKind::Cond(_) = &node.kind {
+            self.add(node.line, Bashism::DoubleBracket);
+        }
+        walk_compound(self, node);
+    }
+
+    fn visit_redirect(&mut self, redirect: &Redirect, line: i32) {
+        if let Some(eof) = &redirect.here_doc_eof {
+            self.here_docs.push(eof.clone());
+        }
+        walk_redirect(self, redirect, line);
+    }
+}
+
+/// Return the end position of the shell word starting at the given position.
+fn word_end(s: &str, start: usize) -> usize {
+    let quoted = quoted(&s[start..]);
+    s[start..]
+        .bytes()
+        .enumerate()
+        .position(|(i, c)| !quoted[i] && (c.is_ascii_whitespace() || b";|&<>()".contains(&c)))
+        .map(|n| start + n)
+        .unwrap_or(s.len())
+}
+
+/// Remove the quoting from a here-document delimiter.
+fn unquote(s: &str) -> String {
+    let (mut single, mut double, mut escaped) = (false, false, false);
+    let mut unquoted = String::new();
+    for c in s.chars() {
+        match c {
+            _ if escaped => {
+                unquoted.push(c);
+                escaped = false;
+            }
+            '\\' if !single => escaped = true,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Return whether each byte of a word is quoted or escaped.
+fn quoted(s: &str) -> Vec<bool> {
+    let (mut single, mut double, mut escaped) = (false, false, false);
+    s.bytes()
+        .map(|c| {
+            let quoted = single || double || escaped;
+            match c {
+                _ if escaped => escaped = false,
+                b'\\' if !single => escaped = true,
+                b'\'' if !double => single = !single,
+                b'"' if !single => double = !double,
+                _ => (),
+            }
+            // opening quotes are also considered quoted
+            quoted || single || double || escaped
+        })
+        .collect()
+}
+
+/// Determine if a string starting with an unquoted `{` begins a brace expansion.
+fn brace_expansion(s: &str, quoted: &[bool]) -> bool {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut sequence = false;
+
+    for (i, c) in bytes.iter().enumerate() {
+        if quoted[i] {
+            continue;
+        }
+        match c {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return sequence || s[1..i].contains("..");
+                }
+            }
+            b',' if depth == 1 => sequence = true,
+            b' ' | b'\t' | b'\n' => return false,
+            _ => (),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn found(s: &str) -> Vec<(usize, Bashism)> {
+        check(s)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.line, f.bashism))
+            .collect()
+    }
+
+    #[test]
+    fn test_posix() {
+        let script = [
+            "#!/bin/sh",
+            "# comment with $'quote' and [[ ]]",
+            "foo() {",
+            "    x=${1:-default}",
+            "    [ \"$x\" = a ] && echo 'a{b,c}' \"$'no'\" \\{d,e}",
+            "    find . -exec echo {} +",
+            "}",
+            "case $1 in a|b) foo ;; esac",
+        ]
+        .join("\n");
+        assert!(found(&script).is_empty());
+    }
+
+    #[test]
+    fn test_bashisms() {
+        use Bashism::*;
+        let script = [
+            "arr=(a b)",
+            "echo ${arr[0]} ${#arr[@]}",
+            "if [[ -n $x ]]; then :; fi",
+            "function foo {",
+            "    local v=1 a=(1)",
+            "}",
+            "diff <(foo) >(bar)",
+            "[ a == b ] || test a == b",
+            "echo {a,b} x{1..3}",
+            "printf $'a\\tb'",
+            "declare -A map",
+        ]
+        .join("\n");
+        assert_eq!(
+            found(&script),
+            [
+                (1, Array),
+                (2, Array),
+                (3, DoubleBracket),
+                (4, FunctionKeyword),
+                (5, Array),
+                (5, Local),
+                (7, ProcessSubstitution),
+                (8, TestEquality),
+                (9, BraceExpansion),
+                (10, AnsiCQuote),
+                (11, Array),
+            ]
+        );
+
+        // here-document bodies are skipped
+        let script = [
+            "cat <<EOF",
+            "it's $'literal'",
+            "EOF",
+            "cat <<-'EOF' | sed -e 's/a/b/'; echo $(( 1 << 2 ))",
+            "\tdon't",
+            "\tEOF",
+            "printf $'a\\tb'",
+        ]
+        .join("\n");
+        assert_eq!(found(&script), [(7, AnsiCQuote)]);
+
+        let findings = check("[[ a ]]\n").unwrap();
+        assert_eq!(
+            findings[0].to_string(),
+            "line 1: [[ ]] is not supported in POSIX sh, use [ ] or test"
+        );
+    }
+
+    #[test]
+    fn test_check_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "a\nlocal x").unwrap();
+        let findings = check_file(file.path()).unwrap();
+        assert_eq!(
+            findings,
+            [Finding {
+                line: 2,
+                bashism: Bashism::Local
+            }]
+        );
+
+        assert!(check_file("/nonexistent/path").is_err());
+        assert!(check("a )").is_err());
+    }
+}
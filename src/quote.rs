@@ -0,0 +1,160 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use nix::libc;
+
+use crate::bash;
+use crate::error::{capture, ErrorKind, ScopedErrorKind};
+use crate::traits::IntoWords;
+use crate::Error;
+
+/// Convert a string allocated by bash into an owned string, freeing the original.
+unsafe fn take(ptr: *mut c_char) -> String {
+    let s = unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() };
+    unsafe { libc::free(ptr as *mut _) };
+    s
+}
+
+/// Convert a string into a C string for quoting.
+fn cstring(s: &[u8]) -> crate::Result<CString> {
+    CString::new(s).map_err(|_| Error::Base("string contains nul byte".into()))
+}
+
+/// Quote a string using single quotes, e.g. `it's` becomes `'it'\''s'`.
+///
+/// Fails for strings containing nul bytes.
+pub fn single<S: AsRef<str>>(s: S) -> crate::Result<String> {
+    let s = cstring(s.as_ref().as_bytes())?;
+    Ok(unsafe { take(bash::sh_single_quote(s.as_ptr())) })
+}
+
+/// Quote a string using double quotes, escaping characters special within them.
+///
+/// Fails for strings containing nul bytes.
+pub fn double<S: AsRef<str>>(s: S) -> crate::Result<String> {
+    let s = cstring(s.as_ref().as_bytes())?;
+    Ok(unsafe { take(bash::sh_double_quote(s.as_ptr())) })
+}
+
+/// Quote a string using ANSI-C quoting, e.g. a tab character becomes `$'\t'`.
+///
+/// Fails for strings containing nul bytes.
+pub fn ansi_c<S: AsRef<str>>(s: S) -> crate::Result<String> {
+    let s = cstring(s.as_ref().as_bytes())?;
+    Ok(unsafe { take(bash::ansic_quote(s.as_ptr() as *mut _, 0, ptr::null_mut())) })
+}
+
+/// Quote a string the same way as `printf %q`.
+///
+/// Strings containing nonprintable characters use ANSI-C quoting while all others escape
+/// special characters with backslashes. Fails for strings containing nul bytes.
+pub fn printf<S: AsRef<str>>(s: S) -> crate::Result<String> {
+    printf_bytes(s.as_ref().as_bytes())
+}
+
+/// Quote raw bytes the same way as `printf %q`, invalid UTF-8 is escaped via ANSI-C quoting.
+pub(crate) fn printf_bytes(s: &[u8]) -> crate::Result<String> {
+    if s.is_empty() {
+        return Ok("''".to_string());
+    }
+
+    let s = cstring(s)?;
+    unsafe {
+        if bash::ansic_shouldquote(s.as_ptr()) != 0 {
+            Ok(take(bash::ansic_quote(s.as_ptr() as *mut _, 0, ptr::null_mut())))
+        } else {
+            Ok(take(bash::sh_backslash_quote(s.as_ptr() as *mut _, ptr::null_mut(), 3)))
+        }
+    }
+}
+
+/// Split a string into words using bash's tokenization rules followed by quote removal.
+///
+/// No expansions are performed and control operators such as `;` or `|` result in errors.
+pub fn split<S: AsRef<str>>(s: S) -> crate::Result<Vec<String>> {
+    let s = s.as_ref();
+    let data = cstring(s.as_bytes())?;
+    let whom = CString::new("quote::split").unwrap();
+
+    let (list, errors) = capture(|| unsafe {
+        let _kind = ScopedErrorKind::new(ErrorKind::Syntax);
+        let eof_reached = bash::EOF_REACHED;
+        let list = bash::parse_string_to_word_list(data.as_ptr() as *mut _, 0, whom.as_ptr());
+        bash::EOF_REACHED = eof_reached;
+        list
+    });
+
+    if let Some(msg) = errors.into_iter().next() {
+        return Err(Error::Base(format!("failed splitting: {s}: {msg}")));
+    }
+
+    Ok(list
+        .into_words(true)
+        .into_iter()
+        .map(|w| {
+            let w = CString::new(w).unwrap();
+            unsafe { take(bash::string_quote_removal(w.as_ptr() as *mut _, 0)) }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single() {
+        assert_eq!(single("").unwrap(), "''");
+        assert_eq!(single("a b").unwrap(), "'a b'");
+        assert_eq!(single("it's").unwrap(), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_double() {
+        assert_eq!(double("a b").unwrap(), r#""a b""#);
+        assert_eq!(double(r#"$a "b" `c` \d"#).unwrap(), r#""\$a \"b\" \`c\` \\d""#);
+    }
+
+    #[test]
+    fn test_ansi_c() {
+        assert_eq!(ansi_c("a\tb\n").unwrap(), r"$'a\tb\n'");
+        assert_eq!(ansi_c("it's").unwrap(), r"$'it\'s'");
+    }
+
+    #[test]
+    fn test_printf() {
+        assert_eq!(printf("").unwrap(), "''");
+        assert_eq!(printf("abc").unwrap(), "abc");
+        assert_eq!(printf("a b$c").unwrap(), r"a\ b\$c");
+        assert_eq!(printf("a\tb").unwrap(), r"$'a\tb'");
+    }
+
+    #[test]
+    fn test_nul() {
+        for f in [single::<&str>, double, ansi_c, printf] {
+            assert!(f("a\0b").is_err());
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        assert!(split("").unwrap().is_empty());
+        assert_eq!(split("  a  b\tc\n").unwrap(), ["a", "b", "c"]);
+        assert_eq!(split(r#"'a b' "c $d" e\ f g"h"i"#).unwrap(), ["a b", "c $d", "e f", "ghi"]);
+        assert!(split("a; b").is_err());
+        assert!(split("'a").is_err());
+        assert!(split("a\0b").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let values = ["", "a b", "it's", "$(x) `y`", "a\tb\n", r"\*?[]{}~#!"];
+        for s in values {
+            for quoted in [single(s), double(s), ansi_c(s), printf(s)] {
+                let quoted = quoted.unwrap();
+                assert_eq!(split(&quoted).unwrap(), [s], "failed quoting {s:?}: {quoted}");
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int};
+use std::os::raw::c_int;
 use std::path::Path;
-use std::{env, mem, process, ptr};
+use std::{env, io, mem, process, ptr};
 
 use nix::{
     sys::signal,
@@ -18,51 +18,35 @@ pub struct Shell {
 
 impl Shell {
     /// Initialize the shell for library use.
-    pub fn init() {
+    ///
+    /// Fails if bash or the error channel can't be set up, e.g. when the temporary directory
+    /// isn't writable.
+    pub fn init() -> crate::Result<()> {
+        let shell = Shell::_init()?;
         SHELL
-            .set(Shell::_init())
-            .expect("failed initializing shell");
+            .set(shell)
+            .map_err(|_| Error::Base("shell already initialized".into()))
     }
 
-    fn _init() -> Self {
-        let shm: *mut c_char;
+    fn _init() -> crate::Result<Self> {
         let name = CString::new("scallop").unwrap();
-        unsafe {
+        let shm = unsafe {
             bash::set_shell_name(name.as_ptr() as *mut _);
             bash::lib_error_handlers(Some(error::bash_error), Some(error::bash_warning));
-            shm = bash::lib_init(4096) as *mut c_char;
-            if shm.is_null() {
-                panic!("failed initializing bash");
-            }
-            SHM.set(shm).expect("shell already initialized");
+            bash::lib_init(error::channel::SHM_SIZE)
+        };
+        if shm.is_null() {
+            return Err(Error::Base("failed initializing bash".into()));
         }
+        error::channel::init(shm).map_err(|e| {
+            let e = io::Error::from_raw_os_error(e as i32);
+            Error::IO(format!("failed initializing error channel: {e}"), e)
+        })?;
 
         // force main pid initialization
         Lazy::force(&PID);
 
-        Shell { _name: name }
-    }
-
-    /// Create an error message in shared memory.
-    pub(crate) fn set_shm_error(msg: &str) {
-        let msg = CString::new(msg).unwrap();
-        let data = msg.into_bytes_with_nul();
-        unsafe {
-            let addr = *SHM.get().expect("uninitialized shell");
-            ptr::copy_nonoverlapping(data.as_ptr(), addr as *mut u8, data.len());
-        }
-    }
-
-    /// Raise an error from shared memory if one exists.
-    pub(crate) fn raise_shm_error() {
-        unsafe {
-            // Note that this is ignored if the shell wasn't initialized, e.g. using scallop as a
-            // shared library for dynamic bash builtins.
-            if let Some(ptr) = SHM.get() {
-                error::bash_error(*ptr);
-                ptr::write_bytes(*ptr, b'\0', 4096);
-            }
-        }
+        Ok(Shell { _name: name })
     }
 
     /// Reset the shell back to a pristine state.
@@ -111,7 +95,11 @@ impl Shell {
 
 static PID: Lazy<Pid> = Lazy::new(getpid);
 static SHELL: OnceCell<Shell> = OnceCell::new();
-static mut SHM: OnceCell<*mut c_char> = OnceCell::new();
+
+/// Returns true if the shell has been initialized, false otherwise.
+pub(crate) fn initialized() -> bool {
+    SHELL.get().is_some()
+}
 
 /// Returns true if currently operating in a subshell, false otherwise.
 pub fn in_subshell() -> bool {
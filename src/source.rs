@@ -1,15 +1,26 @@
-use std::ffi::CString;
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 
 use bitflags::bitflags;
 use once_cell::sync::Lazy;
 
 use crate::builtins::ExecStatus;
-use crate::error::ok_or_error;
+use crate::command::{self, ast::NodeKind};
+use crate::error::{last_error, ok_or_error, BashError, ErrorKind, ScopedErrors};
+use crate::positional::ScopedPositional;
+use crate::source::resolver::Resolved;
 use crate::{bash, Error};
 
+pub mod cache;
+pub mod hooks;
+pub mod resolver;
+
 bitflags! {
-    /// Flag values used with source::string() for altering string evaluation.
+    /// Flag values used with [`Source`] for altering string evaluation.
     pub struct Eval: u32 {
         const NONE = 0;
         const NON_INTERACTIVE = bash::SEVAL_NONINT;
@@ -27,31 +38,346 @@ bitflags! {
 
 static FILE_STR: Lazy<CString> = Lazy::new(|| CString::new("scallop::source::string").unwrap());
 
+/// Builder for sourcing bash code with custom evaluation settings.
+#[derive(Debug, Clone)]
+pub struct Source {
+    flags: Eval,
+    name: Option<String>,
+    line: Option<usize>,
+    errexit: bool,
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self {
+            flags: Eval::NO_FREE,
+            name: None,
+            line: None,
+            errexit: false,
+        }
+    }
+}
+
+impl Source {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the flags used when evaluating strings.
+    ///
+    /// [`Eval::NO_FREE`] is always enabled since the evaluated string is owned by rust.
+    pub fn flags(mut self, flags: Eval) -> Self {
+        self.flags = flags | Eval::NO_FREE;
+        self
+    }
+
+    /// Set the file name used for error messages and `BASH_SOURCE`.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the starting line number used for error messages and `LINENO`.
+    pub fn line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Stop at the first top-level command that fails or raises an error, without enabling
+    /// the `errexit` option.
+    ///
+    /// This is narrower than `set -e` since failures are only checked after each top-level
+    /// command is executed:
+    /// - Commands inside a function call or compound command keep running after a failure,
+    ///   sourcing only stops if the entire command fails, e.g. `f() { false; X=1; }; f`
+    ///   runs to completion.
+    /// - Code sourced by the executed commands, e.g. via the `source` builtin, isn't
+    ///   affected.
+    /// - Commands are executed individually instead of sourcing a file so a top-level
+    ///   `return` fails as if it was used outside a function.
+    ///
+    /// The returned error contains the exit status and the failing line. Evaluation flags
+    /// are ignored in this mode.
+    pub fn errexit(mut self, errexit: bool) -> Self {
+        self.errexit = errexit;
+        self
+    }
+
+    /// Source a string.
+    pub fn string<S: AsRef<str>>(&self, s: S) -> crate::Result<ExecStatus> {
+        self.bytes(s.as_ref().as_bytes())
+    }
+
+    /// Source raw bytes without requiring valid UTF-8.
+    pub fn bytes(&self, data: &[u8]) -> crate::Result<ExecStatus> {
+        match &self.name {
+            Some(name) => {
+                let name = CString::new(name.as_str())
+                    .map_err(|_| Error::Base(format!("invalid name: {name:?}")))?;
+                self.eval(data, &name)
+            }
+            None => self.eval(data, &FILE_STR),
+        }
+    }
+
+    /// Source all data from a reader.
+    pub fn reader<R: Read>(&self, mut reader: R) -> crate::Result<ExecStatus> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data).map_err(|e| {
+            let name = self.name.as_deref().unwrap_or("reader");
+            Error::IO(format!("failed reading: {name}: {e}"), e)
+        })?;
+        self.bytes(&data)
+    }
+
+    /// Source a file.
+    ///
+    /// Files are sourced in the same manner as the `source` builtin unless custom settings
+    /// are used, in which case the file is read and evaluated as a string with its path as
+    /// the default name.
+    pub fn file<P: AsRef<Path>>(&self, path: P) -> crate::Result<ExecStatus> {
+        let _errors = ScopedErrors::new();
+        let requested = path.as_ref();
+        let defaults = self.flags == Eval::NO_FREE && self.line.is_none();
+        let defaults = defaults && self.name.is_none() && !self.errexit;
+        if defaults && cache::enabled() {
+            return cache::source(requested, requested);
+        }
+
+        let (path, data) = match resolver::resolve(requested) {
+            Some(Resolved::Path(path)) => (Cow::Owned(path), None),
+            Some(Resolved::Data(data)) => (Cow::Borrowed(requested), Some(data)),
+            None => (Cow::Borrowed(requested), None),
+        };
+        let path = path.as_ref();
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| Error::Base(format!("invalid path: {path:?}")))?;
+        hooks::enter(requested, path)?;
+
+        if data.is_none() && defaults {
+            let ret = unsafe { bash::source_file(c_path.as_ptr(), 0) };
+
+            // check for more descriptive error, then use return status
+            return ok_or_error()
+                .map_err(|e| e.or_status(ret))
+                .and_then(|status| match ret {
+                    0 => Ok(status),
+                    _ => Err(Error::Base(format!("failed sourcing: {:?}", path))),
+                });
+        }
+
+        let data = match data {
+            Some(data) => data,
+            None => fs::read(path)
+                .map_err(|e| Error::IO(format!("failed reading file: {path:?}: {e}"), e))?,
+        };
+        match &self.name {
+            Some(_) => self.bytes(&data),
+            None => self.eval(&data, &c_path),
+        }
+    }
+
+    fn eval(&self, data: &[u8], name: &CStr) -> crate::Result<ExecStatus> {
+        let _errors = ScopedErrors::new();
+        // named sources get their own call frame with lines starting at 1 by default
+        let (_frame, line) = if name != FILE_STR.as_c_str() {
+            (Some(ScopedFrame::new(name)), self.line.or(Some(1)))
+        } else {
+            (None, self.line)
+        };
+
+        if self.errexit {
+            errexit(data, name, line.unwrap_or(1))
+        } else {
+            let _line = line.map(ScopedLine::new);
+            eval(data, name, self.flags)
+        }
+    }
+}
+
 pub fn string<S: AsRef<str>>(s: S) -> crate::Result<ExecStatus> {
-    let file_ptr = FILE_STR.as_ptr();
-    let s = s.as_ref();
-    let c_str = CString::new(s).unwrap();
+    Source::new().string(s)
+}
+
+/// Source a string as if it was the given file starting at the given line.
+///
+/// The name and line are used in error messages and exposed via `BASH_SOURCE` and `LINENO`
+/// while the string is evaluated.
+pub fn string_at<S: AsRef<str>>(s: S, name: &str, line: usize) -> crate::Result<ExecStatus> {
+    Source::new().name(name).line(line).string(s)
+}
+
+/// Source raw bytes as if they were the given file, without requiring valid UTF-8.
+pub fn bytes(data: &[u8], name: &str) -> crate::Result<ExecStatus> {
+    Source::new().name(name).bytes(data)
+}
+
+/// Source all data from a reader as if it was the given file.
+pub fn reader<R: Read>(reader: R, name: &str) -> crate::Result<ExecStatus> {
+    Source::new().name(name).reader(reader)
+}
+
+fn eval(data: &[u8], name: &CStr, flags: Eval) -> crate::Result<ExecStatus> {
+    let c_str = CString::new(data).map_err(|e| {
+        let name = name.to_string_lossy();
+        let pos = e.nul_position();
+        Error::Base(format!("{name}: nul byte found at position {pos}"))
+    })?;
     let str_ptr = c_str.as_ptr() as *mut _;
-    let ret = unsafe { bash::evalstring(str_ptr, file_ptr, Eval::NO_FREE.bits() as i32) };
+    let ret = unsafe { bash::evalstring(str_ptr, name.as_ptr(), flags.bits() as i32) };
 
     // check for more descriptive error, then use return status
-    ok_or_error().and_then(|status| match ret {
-        0 => Ok(status),
-        _ => Err(Error::Base("failed sourcing string".to_string())),
-    })
+    ok_or_error()
+        .map_err(|e| e.or_status(ret))
+        .and_then(|status| match ret {
+            0 => Ok(status),
+            _ => Err(Error::Base("failed sourcing string".to_string())),
+        })
+}
+
+fn errexit(data: &[u8], name: &CStr, line: usize) -> crate::Result<ExecStatus> {
+    command::parse_with(data, name, line, |cmd| match (cmd.command.execute(), last_error()) {
+        (Ok(_), None) => Ok(()),
+        (result, err) => {
+            let status = match result {
+                Err(e) => ExecStatus::from(e),
+                Ok(_) => ExecStatus::Failure(1),
+            };
+            let file = Some(name.to_string_lossy().into_owned());
+            let command = match cmd.command.ast().kind {
+                NodeKind::Simple(cmd) => cmd.name().map(|w| w.as_str().to_string()),
+                _ => None,
+            };
+            // prefer the more descriptive bash error if one exists
+            let mut err = match err {
+                Some(Error::Bash(e)) => e,
+                // system errors keep their underlying source
+                Some(e @ Error::IO(..)) => return Err(e),
+                Some(e) => BashError {
+                    file,
+                    line: Some(cmd.start),
+                    kind: e.kind(),
+                    command,
+                    status: None,
+                    message: e.to_string(),
+                },
+                None => BashError {
+                    message: format!(
+                        "{}: line {}: command failed: {}",
+                        name.to_string_lossy(),
+                        cmd.start,
+                        cmd.command
+                    ),
+                    file,
+                    line: Some(cmd.start),
+                    kind: ErrorKind::Other,
+                    command,
+                    status: None,
+                },
+            };
+            err.status = Some(status);
+            Err(Error::Bash(err))
+        }
+    })?;
+    Ok(ExecStatus::Success)
+}
+
+/// Starting line number for string evaluation, restoring the original value on drop.
+struct ScopedLine {
+    orig: i32,
+}
+
+impl ScopedLine {
+    fn new(line: usize) -> Self {
+        // Evaluating without resetting the line number decrements the current value before
+        // parsing so the first line uses the given value.
+        unsafe {
+            let orig = bash::LINE_NUMBER;
+            bash::LINE_NUMBER = line as i32;
+            Self { orig }
+        }
+    }
+}
+
+impl Drop for ScopedLine {
+    fn drop(&mut self) {
+        unsafe { bash::LINE_NUMBER = self.orig };
+    }
+}
+
+/// Source call frame pushed onto the `BASH_SOURCE`, `BASH_LINENO`, and `FUNCNAME` arrays
+/// in the same manner as the `source` builtin, popped on drop.
+struct ScopedFrame {
+    arrays: Vec<*mut bash::Array>,
+}
+
+impl ScopedFrame {
+    fn new(name: &CStr) -> Self {
+        let lineno = CString::new(unsafe { bash::executing_line_number() }.to_string()).unwrap();
+        let frame = [
+            ("BASH_SOURCE", name),
+            ("BASH_LINENO", lineno.as_c_str()),
+            ("FUNCNAME", SOURCE_STR.as_c_str()),
+        ];
+
+        let mut arrays = vec![];
+        for (var, value) in frame {
+            // arrays are skipped if they were unset, matching bash
+            if let Some(array) = array(var) {
+                // bash copies the value
+                unsafe { bash::array_shift_element(array, value.as_ptr() as *mut _) };
+                arrays.push(array);
+            }
+        }
+
+        Self { arrays }
+    }
+}
+
+impl Drop for ScopedFrame {
+    fn drop(&mut self) {
+        for array in &self.arrays {
+            unsafe { bash::array_dispose_element(bash::array_shift(*array, 1, 0)) };
+        }
+    }
+}
+
+static SOURCE_STR: Lazy<CString> = Lazy::new(|| CString::new("source").unwrap());
+
+/// Return the raw array for a given variable name if it exists.
+fn array(name: &str) -> Option<*mut bash::Array> {
+    let name = CString::new(name).unwrap();
+    let var = unsafe { bash::find_variable(name.as_ptr()).as_ref() }?;
+    match var.attributes as u32 & bash::att_array {
+        0 => None,
+        _ => Some(var.value as *mut bash::Array),
+    }
 }
 
 pub fn file<P: AsRef<Path>>(path: P) -> crate::Result<ExecStatus> {
-    let path = path.as_ref();
-    let c_str = CString::new(path.to_str().unwrap()).unwrap();
-    let str_ptr = c_str.as_ptr();
-    let ret = unsafe { bash::source_file(str_ptr, 0) };
+    Source::new().file(path)
+}
 
-    // check for more descriptive error, then use return status
-    ok_or_error().and_then(|status| match ret {
-        0 => Ok(status),
-        _ => Err(Error::Base(format!("failed sourcing: {:?}", path))),
-    })
+/// Source a file with the given positional parameters, similar to `source file arg1 arg2`.
+///
+/// The original positional parameters are restored afterwards.
+pub fn file_with_args<P: AsRef<Path>>(path: P, args: &[&str]) -> crate::Result<ExecStatus> {
+    let _args = ScopedPositional::new(args)?;
+    file(path)
+}
+
+/// Source a string, stopping at the first command that fails or raises an error.
+///
+/// See [`Source::errexit`] for more information.
+pub fn string_errexit<S: AsRef<str>>(s: S) -> crate::Result<ExecStatus> {
+    Source::new().errexit(true).string(s)
+}
+
+/// Source a file, stopping at the first command that fails or raises an error.
+///
+/// See [`Source::errexit`] for more information.
+pub fn file_errexit<P: AsRef<Path>>(path: P) -> crate::Result<ExecStatus> {
+    Source::new().errexit(true).file(path)
 }
 
 #[cfg(test)]
@@ -60,8 +386,10 @@ mod tests {
 
     use tempfile::NamedTempFile;
 
-    use crate::source;
     use crate::variables::string_value;
+    use crate::{positional, source};
+
+    use super::*;
 
     #[test]
     fn test_source_string() {
@@ -89,6 +417,57 @@ mod tests {
         assert_eq!(string_value("VAR").unwrap(), "1");
     }
 
+    #[test]
+    fn test_source_string_at() {
+        let script = "VAR1=$LINENO\nVAR2=${BASH_SOURCE[0]}:$LINENO\nVAR3=${FUNCNAME[0]}";
+        source::string_at(script, "src/file.rs", 10).unwrap();
+        assert_eq!(string_value("VAR1").unwrap(), "10");
+        assert_eq!(string_value("VAR2").unwrap(), "src/file.rs:11");
+        assert_eq!(string_value("VAR3").unwrap(), "source");
+
+        // call frame is removed afterwards
+        source::string("VAR=${BASH_SOURCE[0]}").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "");
+
+        // errors use the given location
+        let err = source::string_at("a\nb)", "file.sh", 20).unwrap_err();
+        assert!(err.to_string().starts_with("file.sh: line 21: "), "{err}");
+    }
+
+    #[test]
+    fn test_source_bytes() {
+        // invalid UTF-8 is passed through to bash
+        source::bytes(b"# \xff\nVAR=${BASH_SOURCE[0]}:$LINENO", "data.sh").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "data.sh:2");
+
+        // nul bytes are rejected instead of panicking
+        let err = source::bytes(b"VAR=1\0", "data.sh").unwrap_err();
+        assert_eq!(err.to_string(), "data.sh: nul byte found at position 5");
+        assert!(source::string("VAR=\0").is_err());
+    }
+
+    #[test]
+    fn test_source_reader() {
+        let data: &[u8] = b"VAR=1\nlocal x";
+        let err = source::reader(data, "reader.sh").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("local: can only be used in a function"));
+        assert_eq!(string_value("VAR").unwrap(), "1");
+
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            }
+        }
+        let err = source::reader(Failing, "failing.sh").unwrap_err();
+        match &err {
+            Error::IO(_, e) => assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied),
+            e => panic!("invalid error: {e:?}"),
+        }
+    }
+
     #[test]
     fn test_source_file() {
         assert_eq!(string_value("VAR"), None);
@@ -107,6 +486,36 @@ mod tests {
         assert_eq!(string_value("VAR"), None);
     }
 
+    #[test]
+    fn test_source_file_with_args() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "VAR=\"$#:$*\"").unwrap();
+        positional::set(&["orig"]).unwrap();
+
+        source::file_with_args(file.path(), &["a b", "c"]).unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "2:a b c");
+        assert_eq!(positional::get(), ["orig"]);
+
+        source::file_with_args(file.path(), &[]).unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "0:");
+        assert_eq!(positional::get(), ["orig"]);
+    }
+
+    #[test]
+    fn test_source_file_non_utf8() {
+        use std::ffi::OsStr;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OsStr::from_bytes(b"file-\xff.sh"));
+        fs::write(&path, "VAR=1\n").unwrap();
+        source::file(&path).unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "1");
+
+        // paths with nul bytes error out
+        let path = dir.path().join(OsStr::from_bytes(b"a\0b"));
+        assert!(source::file(path).is_err());
+    }
+
     #[test]
     fn test_source_file_error() {
         assert_eq!(string_value("VAR"), None);
@@ -125,4 +534,116 @@ mod tests {
         assert!(source::file(file.path()).is_err());
         assert_eq!(string_value("VAR").unwrap(), "1");
     }
+
+    #[test]
+    fn test_source_string_errexit() {
+        source::string_errexit("VAR1=1\nfoo() { :; }\nfoo").unwrap();
+        assert_eq!(string_value("VAR1").unwrap(), "1");
+
+        // sourcing stops at the first failure
+        let err = source::string_errexit("VAR2=1\n\n# comment\nfalse\nVAR3=1").unwrap_err();
+        match &err {
+            Error::Bash(e) => {
+                assert_eq!(e.status, Some(ExecStatus::Failure(1)));
+                assert_eq!(e.line, Some(4));
+                assert_eq!(e.message, "scallop::source::string: line 4: command failed: false");
+            }
+            e => panic!("invalid error: {e:?}"),
+        }
+        assert_eq!(string_value("VAR2").unwrap(), "1");
+        assert_eq!(string_value("VAR3"), None);
+
+        // bash errors also stop sourcing
+        let err = source::string_errexit("local VAR4\nVAR4=1").unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("local: can only be used in a function"));
+        assert_eq!(string_value("VAR4"), None);
+
+        // system errors are passed through
+        let err = source::string_errexit(": < /nonexistent/path\nVAR11=1").unwrap_err();
+        assert!(matches!(err, Error::IO(..)), "{err:?}");
+        assert_eq!(string_value("VAR11"), None);
+
+        // handled failures don't stop sourcing
+        source::string_errexit("false || VAR5=1\nif false; then :; fi").unwrap();
+        assert_eq!(string_value("VAR5").unwrap(), "1");
+
+        // parser changes made by previous commands apply to following ones
+        source::string_errexit("shopt -s extglob\n[[ ab == @(ab|cd) ]]").unwrap();
+
+        // failures inside functions only stop sourcing when the call fails
+        source::string_errexit("f() { false; VAR6=1; }\nf\nVAR7=1").unwrap();
+        assert_eq!(string_value("VAR6").unwrap(), "1");
+        assert_eq!(string_value("VAR7").unwrap(), "1");
+        let err = source::string_errexit("f() { VAR8=1; false; }\nf\nVAR9=1").unwrap_err();
+        assert_eq!(err.status(), Some(ExecStatus::Failure(1)));
+        assert_eq!(string_value("VAR8").unwrap(), "1");
+        assert_eq!(string_value("VAR9"), None);
+
+        // top-level returns aren't supported
+        let err = source::string_errexit("return 1\nVAR10=1").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("can only `return' from a function"));
+        assert_eq!(string_value("VAR10"), None);
+
+        // errexit isn't enabled
+        assert!(!crate::bash::set_opts().contains("errexit"));
+    }
+
+    #[test]
+    fn test_source_file_errexit() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "VAR=1\nexit_status() {{ return 3; }}\nexit_status\nVAR=2").unwrap();
+        let err = source::file_errexit(file.path()).unwrap_err();
+        assert_eq!(err.status(), Some(ExecStatus::Failure(3)));
+        let path = file.path().to_string_lossy();
+        assert_eq!(err.to_string(), format!("{path}: line 3: command failed: exit_status"));
+        assert_eq!(string_value("VAR").unwrap(), "1");
+
+        assert!(source::file_errexit("/nonexistent/path").is_err());
+
+        // nested source calls don't stop at failures
+        let mut nested = NamedTempFile::new().unwrap();
+        writeln!(nested, "false\nNESTED=1").unwrap();
+        let path = nested.path().to_string_lossy();
+        source::string_errexit(format!("source {path}\nOUTER=1")).unwrap();
+        assert_eq!(string_value("NESTED").unwrap(), "1");
+        assert_eq!(string_value("OUTER").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_source_builder() {
+        // parse without executing
+        Source::new()
+            .flags(Eval::PARSE_ONLY)
+            .string("VAR=1")
+            .unwrap();
+        assert_eq!(string_value("VAR"), None);
+
+        // custom name and line
+        let src = Source::new().name("src/lib.rs").line(5);
+        src.string("VAR=${BASH_SOURCE[0]}:$LINENO").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "src/lib.rs:5");
+        let err = src.clone().errexit(true).string(":\nfalse").unwrap_err();
+        assert_eq!(err.to_string(), "src/lib.rs: line 6: command failed: false");
+
+        // files are evaluated as strings when using custom settings
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "VAR=${{BASH_SOURCE[0]}}:$LINENO").unwrap();
+        Source::new()
+            .name("file.sh")
+            .line(3)
+            .file(file.path())
+            .unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "file.sh:3");
+        Source::new().line(2).file(file.path()).unwrap();
+        let path = file.path().to_string_lossy();
+        assert_eq!(string_value("VAR").unwrap(), format!("{path}:2"));
+        Source::new()
+            .flags(Eval::PARSE_ONLY)
+            .file(file.path())
+            .unwrap();
+    }
 }
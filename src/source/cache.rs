@@ -0,0 +1,350 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+use std::{fs, mem};
+
+use crate::builtins::ExecStatus;
+use crate::command::{self, Command};
+use crate::error::{ok_or_error, ScopedErrors};
+use crate::source::resolver::{self, Resolved};
+use crate::source::{hooks, ScopedFrame};
+use crate::Error;
+
+/// Cache of parsed files that replays their commands when sourced again instead of
+/// reparsing them.
+///
+/// Entries are invalidated automatically when the modification time or size of a file
+/// changes. Since cached commands are executed individually, `return` can't be used outside
+/// of functions at the top level of cached files.
+///
+/// Files are keyed by their canonical path so symlinks and relative paths to the same file
+/// share an entry.
+#[derive(Debug, Default)]
+pub struct Cache {
+    entries: HashMap<PathBuf, Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    stamp: Stamp,
+    commands: Rc<Vec<Command>>,
+}
+
+/// Data used to determine if a cached entry is outdated.
+#[derive(Debug, PartialEq, Eq)]
+enum Stamp {
+    File { modified: SystemTime, len: u64 },
+    Data(Vec<u8>),
+}
+
+/// Return the key used for a path, falling back to the path itself if it doesn't exist, e.g.
+/// for data provided by a resolver.
+fn key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+thread_local! {
+    static CACHE: RefCell<Option<Cache>> = RefCell::new(None);
+}
+
+/// Cache files sourced via the `source` and `.` builtins or [`crate::source::file`] with
+/// default settings, replacing any existing cache.
+pub fn set(cache: Cache) {
+    hooks::install();
+    CACHE.with(|c| c.replace(Some(cache)));
+}
+
+/// Stop caching sourced files, returning the existing cache.
+pub fn take() -> Option<Cache> {
+    CACHE.with(|c| c.take())
+}
+
+/// Return true if a cache is installed.
+pub(crate) fn enabled() -> bool {
+    CACHE.with(|c| c.borrow().is_some())
+}
+
+/// Source a file using the installed cache.
+///
+/// The name is passed to the resolver and hooks while the default path is sourced if the
+/// resolver doesn't replace it.
+pub(crate) fn source(name: &Path, default: &Path) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
+    let (path, data) = match resolver::resolve(name) {
+        Some(Resolved::Path(path)) => (path, None),
+        Some(Resolved::Data(data)) => (name.to_path_buf(), Some(data)),
+        None => (default.to_path_buf(), None),
+    };
+    let path = path.as_path();
+    let io_err = |e: std::io::Error| Error::IO(format!("failed reading file: {path:?}: {e}"), e);
+    let stamp = match &data {
+        Some(data) => Stamp::Data(data.clone()),
+        None => {
+            let meta = fs::metadata(path).map_err(io_err)?;
+            let modified = meta.modified().map_err(io_err)?;
+            Stamp::File {
+                modified,
+                len: meta.len(),
+            }
+        }
+    };
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::Base(format!("invalid path: {path:?}")))?;
+    hooks::enter(name, path)?;
+    let _frame = ScopedFrame::new(&c_path);
+    let key = key(path);
+
+    // the cache isn't borrowed while executing since commands can source other files
+    let cached = CACHE.with(|c| {
+        let cache = c.borrow();
+        let entry = cache.as_ref()?.entries.get(&key)?;
+        (entry.stamp == stamp).then(|| entry.commands.clone())
+    });
+
+    let mut status = 0;
+    match cached {
+        Some(commands) => {
+            for cmd in commands.iter() {
+                // execution can alter commands so replay copies
+                status = execute(cmd.clone());
+            }
+        }
+        None => {
+            let data = match data {
+                Some(data) => data,
+                None => fs::read(path).map_err(io_err)?,
+            };
+            let mut commands = vec![];
+            // commands are run as they're parsed since they can affect the parser
+            command::parse_with(&data, &c_path, 1, |cmd| {
+                commands.push(cmd.command.clone());
+                status = execute(cmd.command);
+                Ok(())
+            })?;
+            let entry = Entry {
+                stamp,
+                commands: Rc::new(commands),
+            };
+            CACHE.with(|c| {
+                if let Some(cache) = c.borrow_mut().as_mut() {
+                    cache.entries.insert(key, entry);
+                }
+            });
+        }
+    }
+
+    // check for more descriptive error, then use return status
+    ok_or_error().and_then(|s| match status {
+        0 => Ok(s),
+        _ => Err(Error::Base(format!("failed sourcing: {path:?}"))),
+    })
+}
+
+/// Cache installed for the duration of a [`Cache::source`] call so nested source calls use
+/// it, restoring the previous cache on drop.
+struct ScopedCache<'a> {
+    cache: &'a mut Cache,
+    prev: Option<Cache>,
+}
+
+impl<'a> ScopedCache<'a> {
+    fn new(cache: &'a mut Cache) -> Self {
+        hooks::install();
+        let prev = CACHE.with(|c| c.replace(Some(mem::take(cache))));
+        Self { cache, prev }
+    }
+}
+
+impl Drop for ScopedCache<'_> {
+    fn drop(&mut self) {
+        let cache = CACHE.with(|c| c.replace(self.prev.take()));
+        *self.cache = cache.unwrap_or_default();
+    }
+}
+
+impl Cache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Source a file, parsing and caching it if it isn't cached or has changed.
+    ///
+    /// Files sourced by the file's commands via the `source` and `.` builtins are cached as
+    /// well. Paths are mapped using the installed resolver, if any, with in-memory data
+    /// cached until it changes.
+    pub fn source<P: AsRef<Path>>(&mut self, path: P) -> crate::Result<ExecStatus> {
+        let path = path.as_ref();
+        let _cache = ScopedCache::new(self);
+        source(path, path)
+    }
+
+    /// Remove the cached entry for a file, returning true if it existed.
+    pub fn invalidate<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        self.entries.remove(&key(path.as_ref())).is_some()
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Return true if a file is cached.
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entries.contains_key(&key(path.as_ref()))
+    }
+
+    /// Return the number of cached files.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if no files are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Execute a command, returning its exit status.
+fn execute(cmd: Command) -> i32 {
+    match cmd.execute() {
+        Ok(_) => 0,
+        Err(e) => i32::from(ExecStatus::from(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use crate::source;
+    use crate::variables::string_value;
+
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let mut cache = Cache::new();
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "shopt -s extglob\nfoo() {{ [[ $1 == @(a|b) ]]; }}\n").unwrap();
+        writeln!(file, "VAR=$((VAR + 1)):${{BASH_SOURCE[0]}}:$LINENO").unwrap();
+        let path = file.path().to_path_buf();
+        let name = path.to_string_lossy();
+
+        cache.source(&path).unwrap();
+        assert!(cache.contains(&path));
+        assert_eq!(string_value("VAR").unwrap(), format!("1:{name}:3"));
+
+        // cached commands are replayed
+        source::string("VAR=1; unset -f foo").unwrap();
+        cache.source(&path).unwrap();
+        assert_eq!(string_value("VAR").unwrap(), format!("2:{name}:3"));
+        source::string("foo a").unwrap();
+
+        // changed files are reparsed
+        writeln!(file, "VAR2=1").unwrap();
+        cache.source(&path).unwrap();
+        assert_eq!(string_value("VAR2").unwrap(), "1");
+
+        // invalidation
+        assert_eq!(cache.len(), 1);
+        assert!(cache.invalidate(&path));
+        assert!(!cache.invalidate(&path));
+        assert!(cache.is_empty());
+        cache.source(&path).unwrap();
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_cache_errors() {
+        let mut cache = Cache::new();
+        assert!(cache.source("/nonexistent/path").is_err());
+
+        // files with syntax errors aren't cached
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "VAR=1\nfi").unwrap();
+        assert!(cache.source(file.path()).is_err());
+        assert!(cache.is_empty());
+        assert_eq!(string_value("VAR").unwrap(), "1");
+
+        // command errors are returned on every source
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "local VAR").unwrap();
+        for _ in 0..2 {
+            let err = cache.source(file.path()).unwrap_err();
+            assert!(err
+                .to_string()
+                .ends_with("local: can only be used in a function"));
+        }
+        assert!(cache.contains(file.path()));
+    }
+
+    #[test]
+    fn test_nested() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.sh"), dir.path().join("b.sh"));
+        fs::write(&a, format!("A=$((A + 1))\n. {} arg\n", b.display())).unwrap();
+        fs::write(&b, "B=$((B + 1))\nARGS=$*\n").unwrap();
+
+        // files sourced by cached files are cached
+        let mut cache = Cache::new();
+        cache.source(&a).unwrap();
+        assert!(cache.contains(&a));
+        assert!(cache.contains(&b));
+        assert_eq!(string_value("B").unwrap(), "1");
+        assert_eq!(string_value("ARGS").unwrap(), "arg");
+
+        // installed caches are used by the builtins and source::file()
+        set(cache);
+        source::string(format!("source {}", a.display())).unwrap();
+        source::file(&b).unwrap();
+        let cache = take().unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(string_value("A").unwrap(), "2");
+        assert_eq!(string_value("B").unwrap(), "3");
+        assert!(take().is_none());
+    }
+
+    #[test]
+    fn test_canonical_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.sh");
+        fs::write(&path, "CANONICAL=$((CANONICAL + 1))\n").unwrap();
+        let link = dir.path().join("link.sh");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+        let relative = dir.path().join("subdir/../file.sh");
+
+        // symlinks and relative paths share the entry for the file
+        let mut cache = Cache::new();
+        for p in [&path, &link, &relative] {
+            cache.source(p).unwrap();
+        }
+        assert_eq!(cache.len(), 1);
+        assert_eq!(string_value("CANONICAL").unwrap(), "3");
+        assert!(cache.contains(&link));
+        assert!(cache.invalidate(&relative));
+        assert!(!cache.contains(&path));
+    }
+
+    #[test]
+    fn test_resolver() {
+        let mut overlay = HashMap::new();
+        let data = b"VAR=${BASH_SOURCE[0]}\n".to_vec();
+        overlay.insert(PathBuf::from("virtual.sh"), Resolved::Data(data));
+        resolver::set(overlay);
+
+        let mut cache = Cache::new();
+        cache.source("virtual.sh").unwrap();
+        assert!(cache.contains("virtual.sh"));
+        assert_eq!(string_value("VAR").unwrap(), "virtual.sh");
+        resolver::clear();
+    }
+}
@@ -0,0 +1,296 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use once_cell::sync::OnceCell;
+
+use crate::builtins::{handle_error, BuiltinFnPtr};
+use crate::positional::ScopedPositional;
+use crate::source::resolver::{self, Resolved};
+use crate::source::{cache, Source};
+use crate::traits::{IntoWords, Words};
+use crate::variables::{array_to_vec, string_value};
+use crate::{bash, command};
+
+/// File about to be sourced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEvent {
+    /// Canonicalized path to the file, falling back to the resolved path if it doesn't exist,
+    /// e.g. for data provided by a resolver.
+    pub path: PathBuf,
+    /// File name as passed to the source call.
+    pub name: PathBuf,
+    /// File containing the `source` call if one exists.
+    pub parent: Option<String>,
+    /// Line of the `source` call.
+    pub line: usize,
+    /// Nesting level with files sourced outside other files starting at 1.
+    pub depth: usize,
+}
+
+/// Identifier for a registered hook used to unregister it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(usize);
+
+type HookFn = Rc<dyn Fn(&SourceEvent) -> crate::Result<()>>;
+
+thread_local! {
+    static HOOKS: RefCell<Vec<(HookId, HookFn)>> = RefCell::new(vec![]);
+    static NEXT_ID: Cell<usize> = Cell::new(0);
+}
+
+/// Register a function called before every file is sourced, whether via the `source` and
+/// `.` builtins or [`crate::source::file`].
+///
+/// Returning an error denies sourcing the file, causing the related source call to fail
+/// with the error.
+pub fn register<F>(func: F) -> HookId
+where
+    F: Fn(&SourceEvent) -> crate::Result<()> + 'static,
+{
+    install();
+    let id = HookId(NEXT_ID.with(|n| n.replace(n.get() + 1)));
+    HOOKS.with(|hooks| hooks.borrow_mut().push((id, Rc::new(func))));
+    id
+}
+
+/// Unregister a hook, returning true if it was registered.
+pub fn unregister(id: HookId) -> bool {
+    HOOKS.with(|hooks| {
+        let mut hooks = hooks.borrow_mut();
+        let len = hooks.len();
+        hooks.retain(|(x, _)| *x != id);
+        hooks.len() != len
+    })
+}
+
+/// Return the number of files currently being sourced.
+///
+/// Bash's own call stack is used instead of tracking calls since it's unwound properly when
+/// errors longjmp out of nested calls.
+fn depth() -> usize {
+    array_to_vec("FUNCNAME")
+        .map(|v| v.iter().filter(|s| *s == "source").count())
+        .unwrap_or_default()
+}
+
+/// Run the registered hooks for a file about to be sourced using the name passed to the
+/// source call and the path it was resolved to.
+pub(crate) fn enter(name: &Path, path: &Path) -> crate::Result<()> {
+    // clone the hooks so they're able to alter the registry
+    let hooks: Vec<_> = HOOKS.with(|hooks| hooks.borrow().iter().map(|(_, f)| f.clone()).collect());
+    if !hooks.is_empty() {
+        let event = SourceEvent {
+            path: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+            name: name.to_path_buf(),
+            parent: array_to_vec("BASH_SOURCE")
+                .ok()
+                .and_then(|v| v.into_iter().next())
+                .filter(|s| !s.is_empty()),
+            line: unsafe { bash::executing_line_number() } as usize,
+            depth: depth() + 1,
+        };
+        for func in hooks {
+            func(&event)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a file name passed to the `source` builtin, searching `PATH` for names without
+/// slashes when the `sourcepath` option is enabled.
+fn resolve(name: &Path) -> PathBuf {
+    if !name.as_os_str().as_bytes().contains(&b'/') && bash::shopt_opts().contains("sourcepath") {
+        let paths = string_value("PATH").unwrap_or_default();
+        let found = paths
+            .split(':')
+            .filter(|s| !s.is_empty())
+            .map(|s| Path::new(s).join(name))
+            .find(|p| p.is_file());
+        if let Some(path) = found {
+            return path;
+        }
+    }
+    name.to_path_buf()
+}
+
+// original function used by the `source` and `.` builtins
+static SOURCE_BUILTIN: OnceCell<BuiltinFnPtr> = OnceCell::new();
+
+/// Replace the `source` and `.` builtins with a wrapper that runs hooks and resolves paths.
+pub(crate) fn install() {
+    SOURCE_BUILTIN.get_or_init(|| {
+        let mut orig = None;
+        for name in ["source", "."] {
+            let name = CString::new(name).unwrap();
+            let builtin = unsafe { bash::builtin_address_internal(name.as_ptr() as *mut _, 1) };
+            let builtin = unsafe { builtin.as_mut() }.expect("missing source builtin");
+            orig = builtin.function.replace(source_builtin as BuiltinFnPtr);
+        }
+        orig.expect("invalid source builtin")
+    });
+}
+
+extern "C" fn source_builtin(list: *mut bash::WordList) -> c_int {
+    let func = SOURCE_BUILTIN.get().expect("uninitialized source hooks");
+    let words = list.into_words(false);
+    let args: Vec<_> = words.iter_os().collect();
+    let (opts, args) = match args.first() {
+        Some(s) if *s == "--" => args.split_at(1),
+        _ => args.split_at(0),
+    };
+
+    // usage errors are handled by the original builtin
+    let (name, args) = match args.split_first() {
+        Some((name, args)) => (*name, args),
+        None => return unsafe { func(list) },
+    };
+
+    let name = Path::new(name);
+    let result = match cache::enabled() {
+        true => {
+            positional(args).and_then(|_args| cache::source(name, &resolve(name)).map(i32::from))
+        }
+        false => source(*func, list, opts, name, args),
+    };
+
+    result.unwrap_or_else(|e| {
+        i32::from(handle_error(command::current().as_deref().unwrap_or("source"), e))
+    })
+}
+
+/// Override the positional parameters for a sourced file if any arguments were passed.
+fn positional(args: &[&OsStr]) -> crate::Result<Option<ScopedPositional>> {
+    if args.is_empty() {
+        Ok(None)
+    } else {
+        ScopedPositional::new_os(args).map(Some)
+    }
+}
+
+/// Source a file using the original builtin after running the resolver and hooks.
+fn source(
+    func: BuiltinFnPtr,
+    list: *mut bash::WordList,
+    opts: &[&OsStr],
+    name: &Path,
+    args: &[&OsStr],
+) -> crate::Result<c_int> {
+    match resolver::resolve(name) {
+        Some(Resolved::Data(data)) => enter(name, name).and_then(|_| {
+            let _args = positional(args)?;
+            let name = name.to_string_lossy();
+            Source::new().name(name).bytes(&data).map(i32::from)
+        }),
+        Some(Resolved::Path(path)) => enter(name, &path).map(|_| {
+            let words: Words = opts
+                .iter()
+                .copied()
+                .chain([path.as_os_str()])
+                .chain(args.iter().copied())
+                .collect();
+            unsafe { func((&words).into()) }
+        }),
+        None => enter(name, &resolve(name)).map(|_| unsafe { func(list) }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::source;
+    use crate::Error;
+
+    use super::*;
+
+    #[test]
+    fn test_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.sh"), dir.path().join("b.sh"));
+        fs::write(&a, format!("A=1\n. {}\n", b.display())).unwrap();
+        fs::write(&b, "B=1\n").unwrap();
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let recorded = events.clone();
+        let id = register(move |e| {
+            recorded.borrow_mut().push(e.clone());
+            Ok(())
+        });
+
+        source::string(format!("source {}", a.display())).unwrap();
+        assert_eq!(string_value("B").unwrap(), "1");
+        source::file(&b).unwrap();
+
+        let events = events.take();
+        let (a_path, b_path) = (fs::canonicalize(&a).unwrap(), fs::canonicalize(&b).unwrap());
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].path, a_path);
+        assert_eq!(events[0].name, a);
+        assert_eq!(events[0].depth, 1);
+        assert_eq!(events[1].path, b_path);
+        assert_eq!(events[1].parent.as_deref(), Some(a.to_str().unwrap()));
+        assert_eq!(events[1].line, 2);
+        assert_eq!(events[1].depth, 2);
+        assert_eq!(events[2].path, b_path);
+        assert_eq!(events[2].depth, 1);
+
+        assert!(unregister(id));
+        assert!(!unregister(id));
+    }
+
+    #[test]
+    fn test_non_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OsStr::from_bytes(b"\xff.sh"));
+        fs::write(&path, "[[ $1 == $'a\\xffb' ]] && NON_UTF8=1\n").unwrap();
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let recorded = events.clone();
+        let id = register(move |e| {
+            recorded.borrow_mut().push(e.name.clone());
+            Ok(())
+        });
+        let dir = dir.path().display();
+        source::string(format!("source $'{dir}/\\xff.sh' $'a\\xffb'")).unwrap();
+        unregister(id);
+
+        assert_eq!(events.take(), [path]);
+        assert_eq!(string_value("NON_UTF8").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_deny() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.sh");
+        fs::write(&path, "VAR=1\n").unwrap();
+
+        register(|e| match e.path.starts_with("/nonexistent") {
+            true => Ok(()),
+            false => Err(Error::Base(format!("denied: {}", e.path.display()))),
+        });
+
+        let err = source::string(format!("source {}", path.display())).unwrap_err();
+        assert!(err.to_string().contains("denied: "));
+        assert!(source::file(&path).is_err());
+
+        // symlinks and relative paths resolve to the denied file
+        let link = dir.path().join("link.sh");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        let relative = dir.path().join("subdir/../file.sh");
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+        for p in [&link, &relative] {
+            let err = source::string(format!("source {}", p.display())).unwrap_err();
+            let canonical = fs::canonicalize(&path).unwrap();
+            assert!(err
+                .to_string()
+                .contains(&format!("denied: {}", canonical.display())));
+        }
+        assert_eq!(string_value("VAR"), None);
+    }
+}
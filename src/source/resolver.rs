@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::source::hooks;
+
+/// Replacement for a file requested to be sourced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolved {
+    /// Source a different file.
+    Path(PathBuf),
+    /// Source in-memory data in place of the file.
+    Data(Vec<u8>),
+}
+
+/// Map paths requested to be sourced to their replacements.
+pub trait Resolver {
+    /// Return the replacement for a path as passed to the `source` builtin or
+    /// [`crate::source::file`], or None to source it normally.
+    fn resolve(&self, path: &Path) -> Option<Resolved>;
+}
+
+impl<F> Resolver for F
+where
+    F: Fn(&Path) -> Option<Resolved>,
+{
+    fn resolve(&self, path: &Path) -> Option<Resolved> {
+        self(path)
+    }
+}
+
+/// In-memory overlay of replacements for specific paths.
+impl Resolver for HashMap<PathBuf, Resolved> {
+    fn resolve(&self, path: &Path) -> Option<Resolved> {
+        self.get(path).cloned()
+    }
+}
+
+thread_local! {
+    static RESOLVER: RefCell<Option<Rc<dyn Resolver>>> = RefCell::new(None);
+}
+
+/// Install a resolver consulted before sourcing files via the `source` and `.` builtins or
+/// [`crate::source::file`], replacing any existing resolver.
+///
+/// Note that in-memory data is evaluated as a string so `return` can't be used outside of
+/// functions within it.
+pub fn set<R: Resolver + 'static>(resolver: R) {
+    hooks::install();
+    RESOLVER.with(|r| r.replace(Some(Rc::new(resolver))));
+}
+
+/// Remove the installed resolver.
+pub fn clear() {
+    RESOLVER.with(|r| r.replace(None));
+}
+
+/// Return the replacement for a path using the installed resolver.
+pub(crate) fn resolve(path: &Path) -> Option<Resolved> {
+    let resolver = RESOLVER.with(|r| r.borrow().clone())?;
+    resolver.resolve(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::source;
+    use crate::variables::string_value;
+
+    use super::*;
+
+    #[test]
+    fn test_overlay() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real.sh");
+        fs::write(&real, "REAL=1\n").unwrap();
+
+        let mut overlay = HashMap::new();
+        let data = b"VAR=${BASH_SOURCE[0]}:$1\n. eclass/other.eclass\n".to_vec();
+        overlay.insert(PathBuf::from("eclass/foo.eclass"), Resolved::Data(data));
+        overlay.insert(PathBuf::from("eclass/other.eclass"), Resolved::Path(real));
+        set(overlay);
+
+        // builtins
+        source::string("source eclass/foo.eclass arg").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "eclass/foo.eclass:arg");
+        assert_eq!(string_value("REAL").unwrap(), "1");
+
+        // rust API
+        source::string("unset VAR REAL").unwrap();
+        source::file("eclass/foo.eclass").unwrap();
+        assert_eq!(string_value("VAR").unwrap(), "eclass/foo.eclass:");
+        assert_eq!(string_value("REAL").unwrap(), "1");
+
+        // unresolved paths are sourced normally
+        assert!(source::file("eclass/nonexistent.eclass").is_err());
+        clear();
+        assert!(source::file("eclass/foo.eclass").is_err());
+    }
+
+    #[test]
+    fn test_closure() {
+        set(|path: &Path| match path.to_str() {
+            Some("virtual.sh") => Some(Resolved::Data(b"local x".to_vec())),
+            _ => None,
+        });
+        let err = source::file("virtual.sh").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("local: can only be used in a function"));
+    }
+}
@@ -0,0 +1,284 @@
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::{fmt, fs};
+
+use crate::error::capture;
+use crate::source::Eval;
+use crate::{bash, Error};
+
+/// Syntax error found while parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    /// Offending token if bash reported one.
+    pub token: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: line {}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl Diagnostic {
+    /// Convert a bash parser error message into a diagnostic.
+    fn parse(file: &str, offset: usize, msg: &str) -> Option<Self> {
+        let msg = msg.strip_prefix(file)?.strip_prefix(": line ")?;
+        let (line, message) = msg.split_once(": ")?;
+        let line: usize = line.parse().ok()?;
+
+        // skip offending source line context that follows syntax errors
+        if message.starts_with('`') && message.ends_with('\'') {
+            return None;
+        }
+
+        let token = message
+            .split_once('`')
+            .and_then(|(_, s)| s.rsplit_once('\''))
+            .map(|(s, _)| s.to_string());
+
+        Some(Diagnostic {
+            file: file.to_string(),
+            line: line + offset,
+            token,
+            message: message.to_string(),
+        })
+    }
+
+    /// Return the offending token if it starts its line.
+    fn leading_token(&self, script: &str) -> Option<&str> {
+        let line = self
+            .line
+            .checked_sub(1)
+            .and_then(|n| script.lines().nth(n))?;
+        let token = self.token.as_deref()?;
+        if line.trim_start().starts_with(token) {
+            Some(token)
+        } else {
+            None
+        }
+    }
+}
+
+// tokens closing compound commands
+const CLOSERS: [&str; 5] = ["fi", "done", "esac", "}", ")"];
+
+/// Determine if a token continues the compound command closed by the given token.
+fn continues(closer: &str, token: &str) -> bool {
+    match closer {
+        "fi" => ["then", "else", "elif"].contains(&token),
+        "done" => token == "do",
+        "esac" => [";;", ";&", ";;&"].contains(&token),
+        _ => false,
+    }
+}
+
+/// Parse a script returning any error messages.
+fn parse(script: &str, name: &CStr) -> crate::Result<Vec<String>> {
+    let flags = Eval::PARSE_ONLY | Eval::NO_FREE | Eval::NO_HISTORY | Eval::RESET_LINE;
+    let code = CString::new(script).map_err(|_| {
+        Error::Base(format!("{}: script contains nul byte", name.to_string_lossy()))
+    })?;
+    let (_, msgs) = capture(|| unsafe {
+        let eof_reached = bash::EOF_REACHED;
+        bash::evalstring(code.as_ptr() as *mut _, name.as_ptr(), flags.bits() as i32);
+        bash::EOF_REACHED = eof_reached;
+    });
+    Ok(msgs
+        .into_iter()
+        .filter(|msg| !msg.contains(": warning: "))
+        .collect())
+}
+
+/// Determine the tokens closing the compound commands left open at the end of a script, with
+/// the innermost construct last.
+fn unclosed(script: &str, name: &CStr) -> crate::Result<Vec<&'static str>> {
+    let eof = |msgs: &[String]| msgs.iter().all(|m| m.contains("unexpected end of file"));
+    let mut closers = vec![];
+    let mut script = script.to_string();
+
+    loop {
+        let msgs = parse(&script, name)?;
+        if msgs.is_empty() || !eof(&msgs) {
+            break;
+        }
+
+        // find the token that closes the innermost construct, with or without a body
+        let mut found = None;
+        'closers: for closer in CLOSERS {
+            for body in ["\n:\n", "\n"] {
+                let closed = format!("{script}{body}{closer}\n");
+                if eof(&parse(&closed, name)?) {
+                    found = Some((closer, closed));
+                    break 'closers;
+                }
+            }
+        }
+
+        match found {
+            Some((closer, closed)) => {
+                closers.push(closer);
+                script = closed;
+            }
+            None => break,
+        }
+    }
+
+    closers.reverse();
+    Ok(closers)
+}
+
+/// Check a string for syntax errors without executing it, similar to `bash -n`.
+pub fn check<S: AsRef<str>>(s: S) -> crate::Result<Vec<Diagnostic>> {
+    check_str(s.as_ref(), "syntax::check")
+}
+
+/// Check a file for syntax errors without executing it, similar to `bash -n`.
+pub fn check_file<P: AsRef<Path>>(path: P) -> crate::Result<Vec<Diagnostic>> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path)
+        .map_err(|e| Error::IO(format!("failed reading file: {path:?}: {e}"), e))?;
+    check_str(&data, &path.to_string_lossy())
+}
+
+fn check_str(script: &str, file: &str) -> crate::Result<Vec<Diagnostic>> {
+    let name = CString::new(file)
+        .map_err(|_| Error::Base(format!("file name contains nul byte: {file:?}")))?;
+    let mut diagnostics = vec![];
+    let mut remaining = script;
+    let mut offset = 0;
+    // tokens closing the constructs left open by previous errors, innermost last
+    let mut open = vec![];
+
+    // Bash stops parsing at the first syntax error so continue with the lines following
+    // each error until the entire script has been parsed. Tokens starting a line that close
+    // or continue the innermost construct left open by a previous error are skipped, so only
+    // the first error inside each construct is reported.
+    while !remaining.is_empty() {
+        let found: Vec<_> = parse(remaining, &name)?
+            .iter()
+            .filter_map(|msg| Diagnostic::parse(file, offset, msg))
+            .collect();
+
+        let (first, last) = match (found.first(), found.last()) {
+            (Some(first), Some(last)) => (first.line - offset, last.line - offset),
+            _ => break,
+        };
+
+        let prefix: String = remaining
+            .split_inclusive('\n')
+            .take(first.saturating_sub(1))
+            .collect();
+        let local = unclosed(&prefix, &name)?;
+        let token = found[0].leading_token(script);
+        match (local.is_empty(), open.last(), token) {
+            (true, Some(&closer), Some(token)) if closer == token => {
+                open.pop();
+            }
+            (true, Some(&closer), Some(token)) if continues(closer, token) => (),
+            _ => {
+                open.extend(local);
+                diagnostics.extend(found);
+            }
+        }
+
+        if last == 0 {
+            break;
+        }
+
+        let consumed: usize = remaining
+            .split_inclusive('\n')
+            .take(last)
+            .map(|s| s.len())
+            .sum();
+        remaining = &remaining[consumed..];
+        offset += last;
+    }
+
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use crate::functions;
+    use crate::variables::string_value;
+
+    use super::*;
+
+    #[test]
+    fn test_valid() {
+        assert!(check("").unwrap().is_empty());
+        assert!(check("a\nb; c\nfoo() { :; }\n").unwrap().is_empty());
+
+        // nothing is executed
+        assert!(check("VAR=1\nfoo() { :; }").unwrap().is_empty());
+        assert_eq!(string_value("VAR"), None);
+        assert!(functions::find("foo").is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let diags = check("a\nb)\nc\nd)\ne\n").unwrap();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].file, "syntax::check");
+        assert_eq!(diags[0].line, 2);
+        assert_eq!(diags[0].token.as_deref(), Some(")"));
+        assert!(diags[0]
+            .message
+            .starts_with("syntax error near unexpected token"));
+        assert_eq!(diags[1].line, 4);
+        assert_eq!(diags[1].token.as_deref(), Some(")"));
+        assert_eq!(
+            diags[1].to_string(),
+            "syntax::check: line 4: syntax error near unexpected token `)'"
+        );
+
+        // unterminated quote
+        let diags = check("a\necho 'b\n").unwrap();
+        assert!(!diags.is_empty());
+        assert_eq!(diags[0].token.as_deref(), Some("'"));
+
+        // closing tokens following an error inside a compound command are skipped
+        let diags = check("foo() {\n  if a; then\n    b)\n  fi\n}\nc)\n").unwrap();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].line, 3);
+        assert_eq!(diags[1].line, 6);
+
+        // errors after a construct is closed are reported
+        let diags = check("a)\nb\n)").unwrap();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].line, 1);
+        assert_eq!(diags[1].line, 3);
+        let diags = check("if a; then\n  b)\nfi\nfi\n").unwrap();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].line, 2);
+        assert_eq!(diags[1].line, 4);
+
+        // as are errors inside constructs opened after a previous error
+        let diags = check("if a; then\n  b)\n  while c; do\n    fi\n  done\nfi\n").unwrap();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[1].line, 4);
+
+        // invalid file names
+        assert!(check_str("a", "file\0name").is_err());
+    }
+
+    #[test]
+    fn test_check_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "if a; then\n  b\nfi fi").unwrap();
+        let diags = check_file(file.path()).unwrap();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].file, file.path().to_string_lossy());
+        assert_eq!(diags[0].line, 3);
+        assert_eq!(diags[0].token.as_deref(), Some("fi"));
+
+        assert!(check_file("/nonexistent/path").is_err());
+    }
+}
@@ -7,5 +7,5 @@ use crate::shell::Shell;
 /// Initialize bash for all test executables.
 #[ctor]
 fn initialize() {
-    Shell::init();
+    Shell::init().unwrap();
 }
@@ -1,5 +1,6 @@
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::c_char;
+use std::os::unix::ffi::OsStrExt;
 use std::ptr;
 
 use crate::bash;
@@ -9,6 +10,19 @@ pub struct Words {
     drop: bool,
 }
 
+impl Words {
+    /// Iterate over the raw words, allowing non-UTF-8 values.
+    pub fn iter_os(&self) -> impl Iterator<Item = &OsStr> {
+        let mut words = unsafe { self.words.as_ref() };
+        std::iter::from_fn(move || {
+            words.map(|w| unsafe {
+                words = w.next.as_ref();
+                OsStr::from_bytes(CStr::from_ptr((*w.word).word).to_bytes())
+            })
+        })
+    }
+}
+
 impl Drop for Words {
     fn drop(&mut self) {
         if self.drop {
@@ -58,7 +72,17 @@ impl IntoWords for *mut bash::WordList {
 
 impl<'a> FromIterator<&'a str> for Words {
     fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
-        let strs: Vec<_> = iter.into_iter().map(|s| CString::new(s).unwrap()).collect();
+        iter.into_iter().map(OsStr::new).collect()
+    }
+}
+
+/// Words are created from the raw bytes, allowing non-UTF-8 values such as paths.
+impl<'a> FromIterator<&'a OsStr> for Words {
+    fn from_iter<I: IntoIterator<Item = &'a OsStr>>(iter: I) -> Self {
+        let strs: Vec<_> = iter
+            .into_iter()
+            .map(|s| CString::new(s.as_bytes()).unwrap())
+            .collect();
         let mut ptrs: Vec<_> = strs.iter().map(|s| s.as_ptr() as *mut c_char).collect();
         ptrs.push(ptr::null_mut());
         let words = unsafe { bash::strvec_to_word_list(ptrs.as_mut_ptr(), 1, 0) };
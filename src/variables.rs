@@ -4,7 +4,7 @@ use std::slice;
 use bitflags::bitflags;
 
 use crate::builtins::ExecStatus;
-use crate::error::ok_or_error;
+use crate::error::{ok_or_error, ErrorKind, ScopedErrorKind, ScopedErrors};
 use crate::traits::*;
 use crate::{bash, Error};
 
@@ -49,9 +49,11 @@ bitflags! {
 }
 
 pub fn unbind<S: AsRef<str>>(name: S) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
     let name = name.as_ref();
     let cstr = CString::new(name).unwrap();
     unsafe {
+        let _kind = ScopedErrorKind::new(ErrorKind::Readonly);
         bash::check_unbind_variable(cstr.as_ptr());
     }
     ok_or_error()
@@ -67,11 +69,15 @@ where
     S1: AsRef<str>,
     S2: AsRef<str>,
 {
+    let _errors = ScopedErrors::new();
     let name = CString::new(name.as_ref()).unwrap();
     let value = CString::new(value.as_ref()).unwrap();
     let val = value.as_ptr() as *mut _;
     let flags = flags.unwrap_or(Assign::NONE).bits() as i32;
-    let var = unsafe { bash::bind_variable(name.as_ptr(), val, flags).as_mut() };
+    let var = unsafe {
+        let _kind = ScopedErrorKind::new(ErrorKind::Readonly);
+        bash::bind_variable(name.as_ptr(), val, flags).as_mut()
+    };
     if let Some(var) = var {
         if let Some(attrs) = attrs {
             var.attributes |= attrs.bits() as i32;
@@ -86,11 +92,15 @@ pub fn bind_global<S: AsRef<str>>(
     flags: Option<Assign>,
     attrs: Option<Attr>,
 ) -> crate::Result<ExecStatus> {
+    let _errors = ScopedErrors::new();
     let name = CString::new(name.as_ref()).unwrap();
     let value = CString::new(value.as_ref()).unwrap();
     let val = value.as_ptr() as *mut _;
     let flags = flags.unwrap_or(Assign::NONE).bits() as i32;
-    let var = unsafe { bash::bind_global_variable(name.as_ptr(), val, flags).as_mut() };
+    let var = unsafe {
+        let _kind = ScopedErrorKind::new(ErrorKind::Readonly);
+        bash::bind_global_variable(name.as_ptr(), val, flags).as_mut()
+    };
     if let Some(var) = var {
         if let Some(attrs) = attrs {
             var.attributes |= attrs.bits() as i32;
@@ -272,7 +282,7 @@ pub fn array_to_vec<S: AsRef<str>>(name: S) -> crate::Result<Vec<String>> {
         let str_array = bash::array_to_argv(array_ptr, &mut count);
         strings = slice::from_raw_parts(str_array, count as usize)
             .iter()
-            .map(|s| String::from(CStr::from_ptr(*s).to_str().unwrap()))
+            .map(|s| CStr::from_ptr(*s).to_string_lossy().into_owned())
             .collect();
         bash::strvec_dispose(str_array);
     }
@@ -313,8 +323,10 @@ mod tests {
         assert_eq!(string_value("VAR").unwrap(), "1");
         let err = bind("VAR", "1", None, None).unwrap_err();
         assert_eq!(err.to_string(), "VAR: readonly variable");
+        assert_eq!(err.kind(), ErrorKind::Readonly);
         let err = unbind("VAR").unwrap_err();
         assert_eq!(err.to_string(), "VAR: cannot unset: readonly variable");
+        assert_eq!(err.kind(), ErrorKind::Readonly);
     }
 
     #[test]